@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+/// Typed index into an `Arena`.
+pub trait ArenaId: Copy + Eq + Hash {
+    fn from_index(index: usize) -> Self;
+    fn index(self) -> usize;
+}
+
+macro_rules! arena_id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(u32);
+
+        impl ArenaId for $name {
+            fn from_index(index: usize) -> Self {
+                Self(index as u32)
+            }
+
+            fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    };
+}
+
+arena_id!(
+    /// A node of the system.
+    NodeId
+);
+arena_id!(
+    /// A message sent over the network.
+    MessageId
+);
+arena_id!(
+    /// A message between a node and its user.
+    LocalMessageId
+);
+arena_id!(
+    /// A timer set by a node.
+    TimerId
+);
+
+/// Entities of one kind addressed by typed ids. Entities are never removed, so ids stay valid.
+/// The name of every entity in the trace is interned: it is stored once and looked up
+/// only when the trace is read or the name is shown.
+#[derive(Clone, Debug)]
+pub struct Arena<I, T> {
+    items: Vec<T>,
+    names: Vec<String>,
+    ids: HashMap<String, I>,
+    _id: PhantomData<I>,
+}
+
+impl<I: ArenaId, T> Default for Arena<I, T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            names: Vec::new(),
+            ids: HashMap::new(),
+            _id: PhantomData,
+        }
+    }
+}
+
+impl<I: ArenaId, T> Arena<I, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entity. If an entity with the same name exists, it is replaced.
+    pub fn insert(&mut self, name: String, item: T) -> I {
+        self.insert_with(name, |_| item)
+    }
+
+    /// Same as `insert`, for entities that store their own id.
+    pub fn insert_with(&mut self, name: String, make: impl FnOnce(I) -> T) -> I {
+        if let Some(&id) = self.ids.get(&name) {
+            self.items[id.index()] = make(id);
+            return id;
+        }
+        let id = I::from_index(self.items.len());
+        self.ids.insert(name.clone(), id);
+        self.names.push(name);
+        self.items.push(make(id));
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<I> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: I) -> &str {
+        &self.names[id.index()]
+    }
+
+    /// Ids in the order the entities were added.
+    pub fn ids(&self) -> impl Iterator<Item = I> {
+        (0..self.items.len()).map(I::from_index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (I, &T)> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (I::from_index(index), item))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items.iter_mut()
+    }
+}
+
+impl<I: ArenaId, T> Index<I> for Arena<I, T> {
+    type Output = T;
+
+    fn index(&self, id: I) -> &T {
+        &self.items[id.index()]
+    }
+}
+
+impl<I: ArenaId, T> IndexMut<I> for Arena<I, T> {
+    fn index_mut(&mut self, id: I) -> &mut T {
+        &mut self.items[id.index()]
+    }
+}
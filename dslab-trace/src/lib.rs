@@ -0,0 +1,27 @@
+//! Model of a trace of a distributed system run: nodes, messages, timers and network rules,
+//! without any rendering. The state of the system at any moment can be queried:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), String> {
+//! let trace = dslab_trace::Trace::read("examples/ping-pong.txt")?;
+//! let state = trace.state_at(3.2);
+//! for msg in state.in_flight() {
+//!     println!("{} is in flight", trace.messages.name(msg));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Viewers that move through a trace event by event use a `Replay`, as `state_at` does.
+
+pub mod arena;
+pub mod log_entities;
+pub mod network;
+pub mod reader;
+pub mod replay;
+pub mod time;
+pub mod trace;
+
+pub use replay::Replay;
+pub use time::SimTime;
+pub use trace::{Trace, TraceState};
@@ -43,6 +43,19 @@ pub enum LogEntry {
         time: f64,
         msg_id: String,
     },
+    /// A copy of a message reached the destination, which will handle it later,
+    /// e.g. when it is connected again. `MessageReceived` marks the handling.
+    MessageQueued {
+        time: f64,
+        msg_id: String,
+    },
+    /// The network changed the payload of a message in flight.
+    MessageCorrupted {
+        time: f64,
+        msg_id: String,
+        original_data: String,
+        delivered_data: String,
+    },
     NodeConnected {
         time: f64,
         node: String,
@@ -103,12 +116,23 @@ pub enum LogEntry {
     },
     NetworkPartition {
         time: f64,
-        group1: Vec<String>,
-        group2: Vec<String>,
+        #[serde(flatten)]
+        groups: PartitionGroups,
     },
     NetworkReset {
         time: f64,
     },
+    /// Parameters of the network model changed, only the given ones are updated.
+    /// With `from` and `to` the change applies to that link, otherwise to the whole network.
+    NetworkConfigChanged {
+        time: f64,
+        #[serde(default)]
+        from: Option<String>,
+        #[serde(default)]
+        to: Option<String>,
+        #[serde(flatten)]
+        params: NetworkParams,
+    },
     ProcessStateUpdated {
         time: f64,
         node: String,
@@ -117,6 +141,47 @@ pub enum LogEntry {
     },
 }
 
+/// Groups of nodes that can not reach each other. Old traces list exactly two groups
+/// as `group1` and `group2`, newer ones list any number of them as `groups`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum PartitionGroups {
+    Many {
+        groups: Vec<Vec<String>>,
+    },
+    Pair {
+        group1: Vec<String>,
+        group2: Vec<String>,
+    },
+}
+
+impl PartitionGroups {
+    pub fn into_groups(self) -> Vec<Vec<String>> {
+        match self {
+            PartitionGroups::Many { groups } => groups,
+            PartitionGroups::Pair { group1, group2 } => vec![group1, group2],
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Vec<String>> {
+        let (many, pair) = match self {
+            PartitionGroups::Many { groups } => (groups.as_slice(), None),
+            PartitionGroups::Pair { group1, group2 } => (&[][..], Some([group1, group2])),
+        };
+        many.iter().chain(pair.into_iter().flatten())
+    }
+}
+
+/// Parameters of the network model. Delays are in seconds, rates are probabilities.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct NetworkParams {
+    pub min_delay: Option<f64>,
+    pub max_delay: Option<f64>,
+    pub drop_rate: Option<f64>,
+    pub duplication_rate: Option<f64>,
+    pub corruption_rate: Option<f64>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Message {
     pub tip: String,
@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    arena::{Arena, NodeId},
+    log_entities::NetworkParams,
+    trace::EventKind,
+};
+
+/// The rule of the network model that made a message disappear.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DropReason {
+    LinkDisabled,
+    DestDropsIncoming,
+    SrcDropsOutgoing,
+    Partition,
+    /// The trace has no delivery for the message although no rule applied.
+    Lost,
+}
+
+impl DropReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            DropReason::LinkDisabled => "link disabled",
+            DropReason::DestDropsIncoming => "dest drops incoming",
+            DropReason::SrcDropsOutgoing => "src drops outgoing",
+            DropReason::Partition => "partition",
+            DropReason::Lost => "lost by network",
+        }
+    }
+}
+
+/// Rules of the network that are in effect at some moment.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkState {
+    pub drop_outgoing: HashSet<NodeId>,
+    pub drop_incoming: HashSet<NodeId>,
+    pub disabled_links: HashSet<(NodeId, NodeId)>,
+    /// Groups of nodes that are currently cut off from each other, see `connected_groups`.
+    /// `None` while all nodes are connected.
+    pub partition: Option<Vec<Vec<NodeId>>>,
+    /// Parameters of the whole network.
+    pub params: NetworkParams,
+    /// Parameters set for single links, they take precedence over `params`.
+    pub link_params: HashMap<(NodeId, NodeId), NetworkParams>,
+}
+
+impl NetworkState {
+    /// Applies a network event. Other events are ignored, `false` is returned for them.
+    pub fn apply<T>(&mut self, event: &EventKind, nodes: &Arena<NodeId, T>) -> bool {
+        match event {
+            EventKind::LinkDisabled(from, to) => {
+                self.disabled_links.insert((*from, *to));
+                self.update_partition(nodes);
+            }
+            EventKind::LinkEnabled(from, to) => {
+                self.disabled_links.remove(&(*from, *to));
+                self.update_partition(nodes);
+            }
+            EventKind::DropIncoming(node) => {
+                self.drop_incoming.insert(*node);
+            }
+            EventKind::PassIncoming(node) => {
+                self.drop_incoming.remove(node);
+            }
+            EventKind::DropOutgoing(node) => {
+                self.drop_outgoing.insert(*node);
+            }
+            EventKind::PassOutgoing(node) => {
+                self.drop_outgoing.remove(node);
+            }
+            EventKind::NetworkPartition(groups) => {
+                for (i, group1) in groups.iter().enumerate() {
+                    for group2 in &groups[i + 1..] {
+                        for &node1 in group1 {
+                            for &node2 in group2 {
+                                self.disabled_links.insert((node1, node2));
+                                self.disabled_links.insert((node2, node1));
+                            }
+                        }
+                    }
+                }
+                self.update_partition(nodes);
+            }
+            EventKind::NetworkReset => {
+                self.partition = None;
+                self.drop_incoming.clear();
+                self.drop_outgoing.clear();
+                self.disabled_links.clear();
+            }
+            EventKind::NetworkConfigChanged(None, params) => self.params.update(params),
+            EventKind::NetworkConfigChanged(Some(link), params) => {
+                self.link_params.entry(*link).or_default().update(params)
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Finds the groups of nodes that the disabled links cut off from each other.
+    fn update_partition<T>(&mut self, nodes: &Arena<NodeId, T>) {
+        if self.disabled_links.is_empty() {
+            self.partition = None;
+            return;
+        }
+        let links = &self.disabled_links;
+        let groups = connected_groups(nodes.ids(), |a, b| {
+            links.contains(&(a, b)) && links.contains(&(b, a))
+        });
+        self.partition = (groups.len() > 1).then_some(groups);
+    }
+
+    /// Decides whether the network drops a message sent from `src` to `dest`.
+    /// Messages a node sends to itself never leave it, so they are never dropped.
+    pub fn drop_reason(&self, src: NodeId, dest: NodeId) -> Option<DropReason> {
+        if src == dest {
+            return None;
+        }
+        if self.drop_outgoing.contains(&src) {
+            return Some(DropReason::SrcDropsOutgoing);
+        }
+        if self.drop_incoming.contains(&dest) {
+            return Some(DropReason::DestDropsIncoming);
+        }
+        if self.disabled_links.contains(&(src, dest)) {
+            let partitioned = self.partition.as_ref().is_some_and(|groups| {
+                !groups
+                    .iter()
+                    .any(|group| group.contains(&src) && group.contains(&dest))
+            });
+            return Some(if partitioned {
+                DropReason::Partition
+            } else {
+                DropReason::LinkDisabled
+            });
+        }
+        None
+    }
+
+    /// Parameters in effect for the link from `src` to `dest`.
+    pub fn link_config(&self, src: NodeId, dest: NodeId) -> NetworkParams {
+        let mut params = self.params.clone();
+        if let Some(link_params) = self.link_params.get(&(src, dest)) {
+            params.update(link_params);
+        }
+        params
+    }
+}
+
+impl NetworkParams {
+    /// Overrides the parameters that are set in `changes`.
+    pub fn update(&mut self, changes: &NetworkParams) {
+        let fields = [
+            (&mut self.min_delay, changes.min_delay),
+            (&mut self.max_delay, changes.max_delay),
+            (&mut self.drop_rate, changes.drop_rate),
+            (&mut self.duplication_rate, changes.duplication_rate),
+            (&mut self.corruption_rate, changes.corruption_rate),
+        ];
+        for (field, change) in fields {
+            if change.is_some() {
+                *field = change;
+            }
+        }
+    }
+}
+
+/// Splits the nodes into groups that are cut off from each other: nodes are in one group if
+/// they are not `cut` from each other, directly or through other nodes of the group. So a node
+/// that no partition lists keeps its group connected, and overlapping partitions combine
+/// into the groups that none of them separates. Groups are sorted and ordered by their first node.
+pub fn connected_groups<T: Copy + Ord>(
+    nodes: impl IntoIterator<Item = T>,
+    cut: impl Fn(T, T) -> bool,
+) -> Vec<Vec<T>> {
+    let mut rest: Vec<T> = nodes.into_iter().collect();
+    rest.sort();
+    let mut groups = Vec::new();
+    while !rest.is_empty() {
+        let mut group = vec![rest.remove(0)];
+        let mut next = 0;
+        while next < group.len() {
+            let node = group[next];
+            let (joined, left) = rest.into_iter().partition(|&other| !cut(node, other));
+            rest = left;
+            group.extend(joined);
+            next += 1;
+        }
+        group.sort();
+        groups.push(group);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{arena::ArenaId, Trace};
+
+    fn node(index: usize) -> NodeId {
+        NodeId::from_index(index)
+    }
+
+    #[test]
+    fn drop_reason_follows_the_order_of_rules() {
+        let (a, b) = (node(0), node(1));
+        let mut network = NetworkState::default();
+        assert_eq!(network.drop_reason(a, b), None);
+        network.partition = Some(vec![vec![a], vec![b]]);
+        network.disabled_links.insert((a, b));
+        assert_eq!(network.drop_reason(a, b), Some(DropReason::Partition));
+        assert_eq!(network.drop_reason(b, a), None);
+        network.partition = None;
+        assert_eq!(network.drop_reason(a, b), Some(DropReason::LinkDisabled));
+        network.drop_incoming.insert(b);
+        assert_eq!(
+            network.drop_reason(a, b),
+            Some(DropReason::DestDropsIncoming)
+        );
+        network.drop_outgoing.insert(a);
+        assert_eq!(
+            network.drop_reason(a, b),
+            Some(DropReason::SrcDropsOutgoing)
+        );
+    }
+
+    #[test]
+    fn messages_to_self_are_never_dropped() {
+        let a = node(0);
+        let mut network = NetworkState::default();
+        network.drop_outgoing.insert(a);
+        network.drop_incoming.insert(a);
+        network.disabled_links.insert((a, a));
+        assert_eq!(network.drop_reason(a, a), None);
+    }
+
+    #[test]
+    fn disabled_link_inside_a_partition_group_is_not_a_partition() {
+        let (a, b, c) = (node(0), node(1), node(2));
+        let network = NetworkState {
+            partition: Some(vec![vec![a, b], vec![c]]),
+            disabled_links: [(a, b)].into(),
+            ..Default::default()
+        };
+        assert_eq!(network.drop_reason(a, b), Some(DropReason::LinkDisabled));
+    }
+
+    fn groups(groups: &[&[usize]]) -> Vec<Vec<NodeId>> {
+        groups
+            .iter()
+            .map(|group| group.iter().map(|&index| node(index)).collect())
+            .collect()
+    }
+
+    /// Applies the partitions to a network of `count` nodes and returns its groups.
+    fn partition(count: usize, partitions: &[&[&[usize]]]) -> NetworkState {
+        let mut nodes: Arena<NodeId, ()> = Arena::new();
+        for i in 0..count {
+            nodes.insert(i.to_string(), ());
+        }
+        let mut network = NetworkState::default();
+        for &partition in partitions {
+            network.apply(&EventKind::NetworkPartition(groups(partition)), &nodes);
+        }
+        network
+    }
+
+    #[test]
+    fn connected_groups_join_nodes_through_others() {
+        let cut = |a: u32, b: u32| (a.min(b), a.max(b)) == (1, 3);
+        assert_eq!(connected_groups([3, 2, 1], cut), [vec![1, 2, 3]]);
+        let cut = |a: u32, b: u32| a == 3 || b == 3;
+        assert_eq!(connected_groups([3, 2, 1], cut), [vec![1, 2], vec![3]]);
+        assert!(connected_groups([], |_: u32, _| true).is_empty());
+    }
+
+    #[test]
+    fn partition_splits_groups() {
+        let network = partition(4, &[&[&[0, 1], &[2, 3]]]);
+        assert_eq!(network.partition, Some(groups(&[&[0, 1], &[2, 3]])));
+        assert_eq!(
+            network.drop_reason(node(0), node(2)),
+            Some(DropReason::Partition)
+        );
+        assert_eq!(network.drop_reason(node(0), node(1)), None);
+    }
+
+    #[test]
+    fn overlapping_partitions_combine() {
+        let network = partition(4, &[&[&[0, 1], &[2, 3]], &[&[0, 2], &[1, 3]]]);
+        assert_eq!(network.partition, Some(groups(&[&[0], &[1], &[2], &[3]])));
+    }
+
+    #[test]
+    fn partition_keeps_unlisted_nodes_in_their_groups() {
+        // 1 is not listed and no link between 0 and 1 is disabled, so they stay together
+        let network = partition(3, &[&[&[0, 1], &[2]], &[&[0], &[2]]]);
+        assert_eq!(network.partition, Some(groups(&[&[0, 1], &[2]])));
+        assert_eq!(network.drop_reason(node(0), node(1)), None);
+        // 2 still connects 0 and 1 with 3 and 4
+        let network = partition(5, &[&[&[0, 1, 2], &[3, 4]], &[&[0], &[1, 3]]]);
+        assert_eq!(network.partition, Some(groups(&[&[0, 1, 2], &[3, 4]])));
+        assert_eq!(
+            network.drop_reason(node(0), node(1)),
+            Some(DropReason::LinkDisabled)
+        );
+        // a single group is not cut off from anything
+        let network = partition(5, &[&[&[0, 3]]]);
+        assert_eq!(network.partition, None);
+        assert!(network.disabled_links.is_empty());
+    }
+
+    #[test]
+    fn enabled_links_join_groups_again() {
+        let mut nodes: Arena<NodeId, ()> = Arena::new();
+        let (a, b) = (nodes.insert("a".into(), ()), nodes.insert("b".into(), ()));
+        let mut network = NetworkState::default();
+        network.apply(&EventKind::LinkDisabled(a, b), &nodes);
+        assert_eq!(network.partition, None);
+        network.apply(&EventKind::LinkDisabled(b, a), &nodes);
+        assert_eq!(network.partition, Some(vec![vec![a], vec![b]]));
+        network.apply(&EventKind::LinkEnabled(a, b), &nodes);
+        assert_eq!(network.partition, None);
+        assert_eq!(network.drop_reason(b, a), Some(DropReason::LinkDisabled));
+    }
+
+    fn params(min_delay: Option<f64>, drop_rate: Option<f64>) -> NetworkParams {
+        NetworkParams {
+            min_delay,
+            drop_rate,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update_changes_only_the_given_params() {
+        let mut network = params(Some(0.1), Some(0.05));
+        network.update(&params(None, Some(0.2)));
+        assert_eq!(network, params(Some(0.1), Some(0.2)));
+        network.update(&NetworkParams::default());
+        assert_eq!(network, params(Some(0.1), Some(0.2)));
+    }
+
+    #[test]
+    fn link_params_take_precedence_over_network_params() {
+        let mut nodes: Arena<NodeId, ()> = Arena::new();
+        let (a, b) = (nodes.insert("a".into(), ()), nodes.insert("b".into(), ()));
+        let mut network = NetworkState::default();
+        let changes = [
+            (None, params(Some(0.1), Some(0.05))),
+            (Some((a, b)), params(None, Some(0.5))),
+            (None, params(Some(0.2), Some(0.))),
+        ];
+        for (link, params) in changes {
+            network.apply(&EventKind::NetworkConfigChanged(link, params), &nodes);
+        }
+        assert_eq!(network.link_config(a, b), params(Some(0.2), Some(0.5)));
+        assert_eq!(network.link_config(b, a), params(Some(0.2), Some(0.)));
+    }
+
+    fn read(log: &str) -> Result<Trace, String> {
+        let nodes = [
+            r#"{"NodeStarted":{"time":0.0,"node":"a","node_id":0}}"#,
+            r#"{"NodeStarted":{"time":0.0,"node":"b","node_id":1}}"#,
+        ];
+        let entries = nodes
+            .iter()
+            .chain([&log])
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        Trace::from_entries(entries)
+    }
+
+    #[test]
+    fn config_changes_are_parsed_with_and_without_a_link() {
+        let trace = read(r#"{"NetworkConfigChanged":{"time":1.0,"drop_rate":0.1}}"#).unwrap();
+        let EventKind::NetworkConfigChanged(link, changes) = &trace.events[2].kind else {
+            panic!("unexpected event {:?}", trace.events[2]);
+        };
+        assert_eq!(*link, None);
+        assert_eq!(*changes, params(None, Some(0.1)));
+
+        let trace =
+            read(r#"{"NetworkConfigChanged":{"time":1.0,"from":"a","to":"b","min_delay":0.3}}"#)
+                .unwrap();
+        let ids = (trace.nodes.id("a").unwrap(), trace.nodes.id("b").unwrap());
+        let EventKind::NetworkConfigChanged(link, changes) = &trace.events[2].kind else {
+            panic!("unexpected event {:?}", trace.events[2]);
+        };
+        assert_eq!(*link, Some(ids));
+        assert_eq!(*changes, params(Some(0.3), None));
+
+        assert!(
+            read(r#"{"NetworkConfigChanged":{"time":1.0,"from":"a","drop_rate":0.1}}"#).is_err()
+        );
+        assert!(read(r#"{"NetworkConfigChanged":{"time":1.0,"from":"a","to":"c"}}"#).is_err());
+    }
+}
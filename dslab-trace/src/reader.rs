@@ -0,0 +1,24 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use crate::log_entities::LogEntry;
+
+/// Reads a trace with one JSON-encoded `LogEntry` per line. Empty lines are skipped.
+pub fn read_log(filename: &str) -> Result<Vec<LogEntry>, String> {
+    let file = File::open(filename).map_err(|err| format!("cannot open {}: {}", filename, err))?;
+    let reader = BufReader::new(file);
+
+    let mut events = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| format!("cannot read {}: {}", filename, err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line)
+            .map_err(|err| format!("{}:{}: invalid log entry: {}", filename, i + 1, err))?;
+        events.push(event);
+    }
+    Ok(events)
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use crate::{
+    arena::{ArenaId, MessageId},
+    network::{DropReason, NetworkState},
+    time::SimTime,
+    trace::{CopyState, Event, EventKind, MessageState, NodeState, SentMessage, Trace, TraceState},
+};
+
+/// Moves through the events of a trace in order. `Trace::state_at` and the viewer both
+/// replay traces with it, so they agree on the state of the system at any moment.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    /// Index of the next event of `Trace::events` to apply.
+    pub next_event: usize,
+    /// States of the nodes. Their timers include the removed ones, see `state_at`.
+    pub nodes: Vec<NodeState>,
+    pub network: NetworkState,
+    /// Messages sent so far, in the order they were sent.
+    pub sent: Vec<MessageId>,
+    /// Messages the network dropped on sending, by the rule that dropped them.
+    dropped: HashMap<MessageId, DropReason>,
+    mean_latency: f64,
+}
+
+impl Replay {
+    /// Starts before the first event: nodes are connected, but not started yet.
+    pub fn new(trace: &Trace) -> Self {
+        Self {
+            next_event: 0,
+            nodes: trace
+                .nodes
+                .ids()
+                .map(|_| NodeState {
+                    connected: true,
+                    ..Default::default()
+                })
+                .collect(),
+            network: NetworkState::default(),
+            sent: Vec::new(),
+            dropped: HashMap::new(),
+            mean_latency: trace.mean_latency(),
+        }
+    }
+
+    pub fn peek<'a>(&self, trace: &'a Trace) -> Option<&'a Event> {
+        trace.events.get(self.next_event)
+    }
+
+    /// Applies the events up to `time` inclusive and returns them.
+    pub fn advance<'a>(&mut self, trace: &'a Trace, time: SimTime) -> &'a [Event] {
+        let start = self.next_event;
+        while let Some(event) = self.peek(trace).filter(|event| event.time <= time) {
+            self.apply(trace, &event.kind);
+            self.next_event += 1;
+        }
+        &trace.events[start..self.next_event]
+    }
+
+    fn apply(&mut self, trace: &Trace, event: &EventKind) {
+        match event {
+            EventKind::NodeStarted(node) => {
+                self.nodes[node.index()].started = true;
+            }
+            EventKind::MessageSent(id) => {
+                let msg = &trace.messages[*id];
+                if let Some(reason) = self.network.drop_reason(msg.src, msg.dest) {
+                    self.dropped.insert(*id, reason);
+                }
+                self.sent.push(*id);
+            }
+            EventKind::LocalMessageSent(id) | EventKind::LocalMessageReceived(id) => {
+                let node = trace.local_messages[*id].node;
+                self.nodes[node.index()].local_messages.push(*id);
+            }
+            EventKind::NodeConnected(node) => {
+                self.nodes[node.index()].connected = true;
+            }
+            EventKind::NodeDisconnected(node) => {
+                self.nodes[node.index()].connected = false;
+            }
+            EventKind::TimerSet(id) => {
+                let node = trace.timers[*id].node;
+                self.nodes[node.index()].timers.push(*id);
+            }
+            EventKind::NodeStateUpdated(node, node_state) => {
+                self.nodes[node.index()].state.clone_from(node_state);
+            }
+            network_event => {
+                self.network.apply(network_event, &trace.nodes);
+            }
+        }
+    }
+
+    /// The rule that made the network drop a sent message on sending, if any.
+    pub fn drop_reason(&self, id: MessageId) -> Option<DropReason> {
+        self.dropped.get(&id).copied()
+    }
+
+    /// State of a sent message at `time`. Deliveries and drops are stored in the messages,
+    /// so they need no events.
+    pub fn message_state(&self, trace: &Trace, id: MessageId, time: SimTime) -> SentMessage {
+        let msg = &trace.messages[id];
+        let (state, copies) = match self.drop_reason(id) {
+            Some(reason) => (MessageState::Dropped(reason), Vec::new()),
+            None => {
+                let copies: Vec<CopyState> = msg
+                    .copies()
+                    .iter()
+                    .map(|copy| copy.state_at(time))
+                    .collect();
+                let lost = time >= msg.time_lost(self.mean_latency);
+                let state = MessageState::of_copies(&copies, msg.src == msg.dest, lost);
+                (state, copies)
+            }
+        };
+        SentMessage { id, state, copies }
+    }
+
+    /// The system at `time`, which must not be before the applied events. Only the timers
+    /// that are still active at `time` are left.
+    pub fn state_at(&self, trace: &Trace, time: SimTime) -> TraceState {
+        let mut nodes = self.nodes.clone();
+        for node in &mut nodes {
+            node.timers
+                .retain(|&id| trace.timers[id].is_active_at(time));
+        }
+        TraceState {
+            time,
+            nodes,
+            messages: self
+                .sent
+                .iter()
+                .map(|&id| self.message_state(trace, id, time))
+                .collect(),
+            network: self.network.clone(),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, AddAssign, Sub},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A moment of simulated time in seconds. Always `f64`: long simulations need the precision
+/// to keep close events in order. Durations are plain `f64` seconds.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(transparent)]
+pub struct SimTime(f64);
+
+impl SimTime {
+    pub const ZERO: SimTime = SimTime(0.);
+
+    pub fn from_secs(secs: f64) -> Self {
+        Self(secs)
+    }
+
+    pub fn as_secs(self) -> f64 {
+        self.0
+    }
+
+    /// Formats the time in the given unit with `precision` digits after the point.
+    pub fn display(self, unit: TimeUnit, precision: usize) -> String {
+        format!(
+            "{:.*} {}",
+            precision,
+            self.0 * unit.per_second(),
+            unit.suffix()
+        )
+    }
+}
+
+impl From<f64> for SimTime {
+    fn from(secs: f64) -> Self {
+        Self(secs)
+    }
+}
+
+impl PartialEq for SimTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SimTime {}
+
+impl PartialOrd for SimTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SimTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Add<f64> for SimTime {
+    type Output = SimTime;
+
+    fn add(self, secs: f64) -> SimTime {
+        SimTime(self.0 + secs)
+    }
+}
+
+impl AddAssign<f64> for SimTime {
+    fn add_assign(&mut self, secs: f64) {
+        self.0 += secs;
+    }
+}
+
+impl Sub<f64> for SimTime {
+    type Output = SimTime;
+
+    fn sub(self, secs: f64) -> SimTime {
+        SimTime(self.0 - secs)
+    }
+}
+
+/// Seconds between two moments.
+impl Sub for SimTime {
+    type Output = f64;
+
+    fn sub(self, other: SimTime) -> f64 {
+        self.0 - other.0
+    }
+}
+
+impl fmt::Display for SimTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
+    Microseconds,
+}
+
+impl TimeUnit {
+    pub fn per_second(&self) -> f64 {
+        match self {
+            TimeUnit::Seconds => 1.,
+            TimeUnit::Milliseconds => 1e3,
+            TimeUnit::Microseconds => 1e6,
+        }
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            TimeUnit::Seconds => "s",
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Microseconds => "us",
+        }
+    }
+}
@@ -0,0 +1,842 @@
+use crate::{
+    arena::*,
+    log_entities::{self, LogEntry, NetworkParams},
+    network::{DropReason, NetworkState},
+    reader::read_log,
+    replay::Replay,
+    time::SimTime,
+};
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// Id of the node in the trace.
+    pub node_id: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub src: NodeId,
+    pub dest: NodeId,
+    pub tip: String,
+    pub data: String,
+    pub time_sent: SimTime,
+    /// Delivery times of the copies of the message, in the order of the trace.
+    /// More than one copy means the network duplicated the message.
+    pub deliveries: Vec<SimTime>,
+    /// Times the copies reached the destination before it handled them, in the same order.
+    pub queued: Vec<SimTime>,
+    pub time_dropped: Option<SimTime>,
+    pub corruption: Option<Corruption>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Corruption {
+    pub time: SimTime,
+    pub original_data: String,
+    pub delivered_data: String,
+}
+
+impl Message {
+    /// Latency of the first delivered copy.
+    pub fn latency(&self) -> Option<f64> {
+        self.deliveries.first().map(|&time| time - self.time_sent)
+    }
+
+    /// Copies of the message that reached the destination, in the order of `deliveries`.
+    /// Each delivery is matched with the earliest queueing at or before it that is not matched
+    /// yet, a copy that was not queued arrives when it is delivered. Queued copies that are
+    /// never delivered come last.
+    pub fn copies(&self) -> Vec<MessageCopy> {
+        let mut queued = self.queued.clone();
+        queued.sort();
+        let mut matched = vec![false; queued.len()];
+        let mut copies: Vec<MessageCopy> = self
+            .deliveries
+            .iter()
+            .map(|&delivery| {
+                let earliest = (0..queued.len()).find(|&i| !matched[i] && queued[i] <= delivery);
+                let arrival = match earliest {
+                    Some(i) => {
+                        matched[i] = true;
+                        queued[i]
+                    }
+                    None => delivery,
+                };
+                MessageCopy {
+                    arrival,
+                    delivery: Some(delivery),
+                }
+            })
+            .collect();
+        copies.extend(
+            queued
+                .iter()
+                .zip(&matched)
+                .filter(|(_, &matched)| !matched)
+                .map(|(&arrival, _)| MessageCopy {
+                    arrival,
+                    delivery: None,
+                }),
+        );
+        copies
+    }
+
+    /// Times the copies of the message reached the destination, see `copies`.
+    pub fn arrivals(&self) -> Vec<SimTime> {
+        self.copies().iter().map(|copy| copy.arrival).collect()
+    }
+
+    /// Time a message that no copy of reached the destination is lost: its drop time or,
+    /// if the trace has none, `mean_latency` after it is sent, as if it flew as long as
+    /// delivered messages do. A node handles a message to itself at once.
+    pub fn time_lost(&self, mean_latency: f64) -> SimTime {
+        match self.time_dropped {
+            Some(time) => time,
+            None if self.src == self.dest => self.time_sent,
+            None => self.time_sent + mean_latency,
+        }
+    }
+}
+
+/// A copy of a message that reached the destination. The network may deliver several copies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MessageCopy {
+    pub arrival: SimTime,
+    /// Time the destination handled the copy, if it did.
+    pub delivery: Option<SimTime>,
+}
+
+impl MessageCopy {
+    pub fn state_at(&self, time: SimTime) -> CopyState {
+        if self.delivery.is_some_and(|delivery| time >= delivery) {
+            CopyState::Delivered
+        } else if time >= self.arrival {
+            CopyState::Queued
+        } else {
+            CopyState::InFlight
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LocalMessageKind {
+    Sent,
+    Received,
+}
+
+#[derive(Clone, Debug)]
+pub struct LocalMessage {
+    pub node: NodeId,
+    pub tip: String,
+    pub data: String,
+    pub time: SimTime,
+    pub kind: LocalMessageKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct Timer {
+    pub name: String,
+    pub node: NodeId,
+    pub time_set: SimTime,
+    pub delay: f64,
+    /// Time the timer fired or was cancelled.
+    pub time_removed: Option<SimTime>,
+}
+
+impl Timer {
+    /// Whether the timer is set and has not fired or been cancelled by `time`.
+    /// A timer that is never removed stays active until the end of the trace.
+    pub fn is_active_at(&self, time: SimTime) -> bool {
+        time >= self.time_set && self.time_removed.is_none_or(|removed| time < removed)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum EventKind {
+    NodeStarted(NodeId),
+    MessageSent(MessageId),
+    LocalMessageSent(LocalMessageId),
+    LocalMessageReceived(LocalMessageId),
+    NodeConnected(NodeId),
+    NodeDisconnected(NodeId),
+    TimerSet(TimerId),
+    LinkDisabled(NodeId, NodeId),
+    LinkEnabled(NodeId, NodeId),
+    DropIncoming(NodeId),
+    PassIncoming(NodeId),
+    DropOutgoing(NodeId),
+    PassOutgoing(NodeId),
+    NetworkPartition(Vec<Vec<NodeId>>),
+    NetworkReset,
+    /// Parameters of one link or, without a link, of the whole network changed.
+    NetworkConfigChanged(Option<(NodeId, NodeId)>, NetworkParams),
+    NodeStateUpdated(NodeId, String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub time: SimTime,
+    pub kind: EventKind,
+}
+
+/// A run of a distributed system: its entities and the events ordered by time.
+/// Deliveries, drops and timer removals are stored in the entities they belong to.
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+    pub nodes: Arena<NodeId, Node>,
+    pub messages: Arena<MessageId, Message>,
+    pub local_messages: Arena<LocalMessageId, LocalMessage>,
+    pub timers: Arena<TimerId, Timer>,
+    pub events: Vec<Event>,
+}
+
+impl Trace {
+    pub fn read(filename: &str) -> Result<Self, String> {
+        Self::from_entries(read_log(filename)?)
+    }
+
+    pub fn from_entries(entries: Vec<LogEntry>) -> Result<Self, String> {
+        let mut trace = Self::default();
+        for entry in entries {
+            trace.add_entry(entry)?;
+        }
+        trace.events.sort_by_key(|event| event.time);
+        Ok(trace)
+    }
+
+    fn node(&self, name: &str) -> Result<NodeId, String> {
+        self.nodes
+            .id(name)
+            .ok_or_else(|| format!("unknown node {}", name))
+    }
+
+    fn push(&mut self, time: f64, kind: EventKind) {
+        self.events.push(Event {
+            time: time.into(),
+            kind,
+        });
+    }
+
+    fn add_entry(&mut self, entry: LogEntry) -> Result<(), String> {
+        match entry {
+            LogEntry::NodeStarted {
+                time,
+                node,
+                node_id,
+            } => {
+                let node = self.nodes.insert(node, Node { node_id });
+                self.push(time, EventKind::NodeStarted(node));
+            }
+            LogEntry::LocalMessageSent {
+                time,
+                msg_id,
+                node,
+                msg,
+                ..
+            } => {
+                let id =
+                    self.add_local_message(time, msg_id, &node, msg, LocalMessageKind::Sent)?;
+                self.push(time, EventKind::LocalMessageSent(id));
+            }
+            LogEntry::LocalMessageReceived {
+                time,
+                msg_id,
+                node,
+                msg,
+                ..
+            } => {
+                let id =
+                    self.add_local_message(time, msg_id, &node, msg, LocalMessageKind::Received)?;
+                self.push(time, EventKind::LocalMessageReceived(id));
+            }
+            LogEntry::MessageSent {
+                time,
+                msg_id,
+                src_node,
+                dest_node,
+                msg,
+                ..
+            } => {
+                let message = Message {
+                    src: self.node(&src_node)?,
+                    dest: self.node(&dest_node)?,
+                    tip: msg.tip,
+                    data: msg.data,
+                    time_sent: time.into(),
+                    deliveries: Vec::new(),
+                    queued: Vec::new(),
+                    time_dropped: None,
+                    corruption: None,
+                };
+                let id = self.messages.insert(msg_id, message);
+                self.push(time, EventKind::MessageSent(id));
+            }
+            LogEntry::MessageReceived { time, msg_id } => {
+                let id = self
+                    .messages
+                    .id(&msg_id)
+                    .ok_or_else(|| format!("message {} received before sent", msg_id))?;
+                self.messages[id].deliveries.push(time.into());
+            }
+            LogEntry::MessageQueued { time, msg_id } => {
+                let id = self
+                    .messages
+                    .id(&msg_id)
+                    .ok_or_else(|| format!("message {} queued before sent", msg_id))?;
+                self.messages[id].queued.push(time.into());
+            }
+            LogEntry::MessageDropped { time, msg_id } => {
+                let id = self
+                    .messages
+                    .id(&msg_id)
+                    .ok_or_else(|| format!("message {} dropped before sent", msg_id))?;
+                self.messages[id].time_dropped = Some(time.into());
+            }
+            LogEntry::MessageCorrupted {
+                time,
+                msg_id,
+                original_data,
+                delivered_data,
+            } => {
+                let id = self
+                    .messages
+                    .id(&msg_id)
+                    .ok_or_else(|| format!("message {} corrupted before sent", msg_id))?;
+                self.messages[id].corruption = Some(Corruption {
+                    time: time.into(),
+                    original_data,
+                    delivered_data,
+                });
+            }
+            LogEntry::NodeConnected { time, node } => {
+                let node = self.node(&node)?;
+                self.push(time, EventKind::NodeConnected(node));
+            }
+            LogEntry::NodeDisconnected { time, node } => {
+                let node = self.node(&node)?;
+                self.push(time, EventKind::NodeDisconnected(node));
+            }
+            LogEntry::ProcessStarted { .. }
+            | LogEntry::NodeCrashed { .. }
+            | LogEntry::NodeRecovered { .. } => {}
+            LogEntry::TimerSet {
+                time,
+                timer_id,
+                timer_name,
+                node,
+                delay,
+                ..
+            } => {
+                let timer = Timer {
+                    name: timer_name,
+                    node: self.node(&node)?,
+                    time_set: time.into(),
+                    delay,
+                    time_removed: None,
+                };
+                let id = self.timers.insert(timer_id, timer);
+                self.push(time, EventKind::TimerSet(id));
+            }
+            LogEntry::TimerFired { time, timer_id }
+            | LogEntry::TimerCancelled { time, timer_id } => {
+                let id = self
+                    .timers
+                    .id(&timer_id)
+                    .ok_or_else(|| format!("timer {} removed before set", timer_id))?;
+                self.timers[id].time_removed = Some(time.into());
+            }
+            LogEntry::LinkDisabled { time, from, to } => {
+                let link = (self.node(&from)?, self.node(&to)?);
+                self.push(time, EventKind::LinkDisabled(link.0, link.1));
+            }
+            LogEntry::LinkEnabled { time, from, to } => {
+                let link = (self.node(&from)?, self.node(&to)?);
+                self.push(time, EventKind::LinkEnabled(link.0, link.1));
+            }
+            LogEntry::DropIncoming { time, node } => {
+                let node = self.node(&node)?;
+                self.push(time, EventKind::DropIncoming(node));
+            }
+            LogEntry::PassIncoming { time, node } => {
+                let node = self.node(&node)?;
+                self.push(time, EventKind::PassIncoming(node));
+            }
+            LogEntry::DropOutgoing { time, node } => {
+                let node = self.node(&node)?;
+                self.push(time, EventKind::DropOutgoing(node));
+            }
+            LogEntry::PassOutgoing { time, node } => {
+                let node = self.node(&node)?;
+                self.push(time, EventKind::PassOutgoing(node));
+            }
+            LogEntry::NetworkPartition { time, groups } => {
+                let groups = groups
+                    .iter()
+                    .map(|group| group.iter().map(|node| self.node(node)).collect())
+                    .collect::<Result<_, _>>()?;
+                self.push(time, EventKind::NetworkPartition(groups));
+            }
+            LogEntry::NetworkReset { time } => self.push(time, EventKind::NetworkReset),
+            LogEntry::NetworkConfigChanged {
+                time,
+                from,
+                to,
+                params,
+            } => {
+                let link = match (from, to) {
+                    (Some(from), Some(to)) => Some((self.node(&from)?, self.node(&to)?)),
+                    (None, None) => None,
+                    _ => return Err("link config change needs both from and to".to_string()),
+                };
+                self.push(time, EventKind::NetworkConfigChanged(link, params));
+            }
+            LogEntry::ProcessStateUpdated {
+                time, node, state, ..
+            } => {
+                let node = self.node(&node)?;
+                self.push(time, EventKind::NodeStateUpdated(node, state));
+            }
+        }
+        Ok(())
+    }
+
+    fn add_local_message(
+        &mut self,
+        time: f64,
+        id: String,
+        node: &str,
+        msg: log_entities::Message,
+        kind: LocalMessageKind,
+    ) -> Result<LocalMessageId, String> {
+        let local_message = LocalMessage {
+            node: self.node(node)?,
+            tip: msg.tip,
+            data: msg.data,
+            time: time.into(),
+            kind,
+        };
+        Ok(self.local_messages.insert(id, local_message))
+    }
+
+    /// Mean latency of the messages delivered after some time, 1 if there are none.
+    /// Lost messages without drop times are lost after it, see `Message::time_lost`.
+    pub fn mean_latency(&self) -> f64 {
+        let latencies: Vec<f64> = self
+            .messages
+            .values()
+            .filter_map(|msg| msg.latency())
+            .filter(|&latency| latency > 0.)
+            .collect();
+        if latencies.is_empty() {
+            1.
+        } else {
+            latencies.iter().sum::<f64>() / latencies.len() as f64
+        }
+    }
+
+    /// Replays the events up to `time` inclusive, see `Replay`.
+    pub fn state_at(&self, time: impl Into<SimTime>) -> TraceState {
+        let time = time.into();
+        let mut replay = Replay::new(self);
+        replay.advance(self, time);
+        replay.state_at(self, time)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeState {
+    pub started: bool,
+    pub connected: bool,
+    pub state: String,
+    /// Timers that are set and have not fired or been cancelled yet.
+    pub timers: Vec<TimerId>,
+    pub local_messages: Vec<LocalMessageId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageState {
+    /// Some copies did not reach the destination yet, others may be delivered already,
+    /// see `SentMessage::copies`.
+    InFlight,
+    /// All copies reached the destination, some of them are not handled yet.
+    Queued,
+    Delivered,
+    Dropped(DropReason),
+}
+
+impl MessageState {
+    /// State of a message that the network did not drop on sending, given the states of its
+    /// copies. A duplicated message stays in flight until all its copies arrive and is delivered
+    /// when the destination handles all of them. A message without copies is in flight until
+    /// it is `lost`, unless a node sent it to itself.
+    pub fn of_copies(copies: &[CopyState], to_self: bool, lost: bool) -> MessageState {
+        if copies.is_empty() {
+            return if to_self {
+                MessageState::Delivered
+            } else if lost {
+                MessageState::Dropped(DropReason::Lost)
+            } else {
+                MessageState::InFlight
+            };
+        }
+        if copies.iter().all(|&copy| copy == CopyState::Delivered) {
+            MessageState::Delivered
+        } else if copies.contains(&CopyState::InFlight) {
+            MessageState::InFlight
+        } else {
+            MessageState::Queued
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CopyState {
+    InFlight,
+    /// The copy reached the destination, which did not handle it yet.
+    Queued,
+    Delivered,
+}
+
+/// A message sent by some moment and its state at that moment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentMessage {
+    pub id: MessageId,
+    pub state: MessageState,
+    /// States of the copies that reach the destination, see `Message::copies`.
+    pub copies: Vec<CopyState>,
+}
+
+/// The system at some moment of a trace, see `Trace::state_at`.
+#[derive(Clone, Debug)]
+pub struct TraceState {
+    pub time: SimTime,
+    pub(crate) nodes: Vec<NodeState>,
+    /// Messages sent so far, in the order they were sent.
+    pub messages: Vec<SentMessage>,
+    pub network: NetworkState,
+}
+
+impl TraceState {
+    pub fn node(&self, id: NodeId) -> &NodeState {
+        &self.nodes[id.index()]
+    }
+
+    pub fn message(&self, id: MessageId) -> Option<&SentMessage> {
+        self.messages.iter().find(|msg| msg.id == id)
+    }
+
+    pub fn message_state(&self, id: MessageId) -> Option<MessageState> {
+        self.message(id).map(|msg| msg.state)
+    }
+
+    pub fn in_flight(&self) -> impl Iterator<Item = MessageId> + '_ {
+        self.messages
+            .iter()
+            .filter(|msg| msg.state == MessageState::InFlight)
+            .map(|msg| msg.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a trace written as one JSON log entry per line.
+    fn trace(log: &str) -> Trace {
+        let entries = log
+            .lines()
+            .map(|line| serde_json::from_str(line.trim()).unwrap())
+            .collect();
+        Trace::from_entries(entries).unwrap()
+    }
+
+    const NODES: &str = r#"{"NodeStarted":{"time":0.0,"node":"a","node_id":0}}
+        {"NodeStarted":{"time":0.0,"node":"b","node_id":1}}"#;
+
+    fn sent(time: f64, msg_id: &str, src: &str, dest: &str) -> String {
+        format!(
+            r#"{{"MessageSent":{{"time":{time:?},"msg_id":"{msg_id}","src_node":"{src}","src_proc":"p","dest_node":"{dest}","dest_proc":"p","msg":{{"tip":"PING","data":"{{}}"}}}}}}"#
+        )
+    }
+
+    fn entry(kind: &str, time: f64, msg_id: &str) -> String {
+        format!(r#"{{"{kind}":{{"time":{time:?},"msg_id":"{msg_id}"}}}}"#)
+    }
+
+    fn times(times: &[f64]) -> Vec<SimTime> {
+        times.iter().map(|&time| time.into()).collect()
+    }
+
+    fn message(deliveries: &[f64], queued: &[f64]) -> Message {
+        Message {
+            src: NodeId::from_index(0),
+            dest: NodeId::from_index(1),
+            tip: "PING".to_owned(),
+            data: "{}".to_owned(),
+            time_sent: SimTime::ZERO,
+            deliveries: times(deliveries),
+            queued: times(queued),
+            time_dropped: None,
+            corruption: None,
+        }
+    }
+
+    #[test]
+    fn state_at_follows_a_message() {
+        let log = [
+            NODES.to_owned(),
+            sent(1., "0", "a", "b"),
+            entry("MessageQueued", 3., "0"),
+            entry("MessageReceived", 4., "0"),
+        ];
+        let trace = trace(&log.join("\n"));
+        let id = trace.messages.id("0").unwrap();
+        assert_eq!(trace.state_at(0.5).message(id), None);
+        assert_eq!(
+            trace.state_at(2.).message_state(id),
+            Some(MessageState::InFlight)
+        );
+        assert_eq!(trace.state_at(2.).in_flight().collect::<Vec<_>>(), [id]);
+        assert_eq!(
+            trace.state_at(3.2).message_state(id),
+            Some(MessageState::Queued)
+        );
+        assert_eq!(
+            trace.state_at(4.).message_state(id),
+            Some(MessageState::Delivered)
+        );
+        assert_eq!(trace.state_at(4.).in_flight().count(), 0);
+    }
+
+    #[test]
+    fn state_at_of_a_lost_message() {
+        let log = [
+            NODES.to_owned(),
+            sent(1., "0", "a", "b"),
+            entry("MessageDropped", 2., "0"),
+            sent(1., "1", "b", "a"),
+            sent(1., "2", "a", "b"),
+            entry("MessageReceived", 1.5, "2"),
+        ];
+        let trace = trace(&log.join("\n"));
+        let (lost, unknown) = (
+            trace.messages.id("0").unwrap(),
+            trace.messages.id("1").unwrap(),
+        );
+        assert_eq!(trace.mean_latency(), 0.5);
+        let lost_state = Some(MessageState::Dropped(DropReason::Lost));
+        assert_eq!(
+            trace.state_at(1.5).message_state(lost),
+            Some(MessageState::InFlight)
+        );
+        assert_eq!(trace.state_at(2.).message_state(lost), lost_state);
+        // without a drop time a message that never arrives flies for the mean latency
+        assert_eq!(
+            trace.state_at(1.4).message_state(unknown),
+            Some(MessageState::InFlight)
+        );
+        assert_eq!(trace.state_at(1.5).message_state(unknown), lost_state);
+    }
+
+    #[test]
+    fn replay_in_steps_agrees_with_state_at() {
+        let log = [
+            NODES.to_owned(),
+            sent(1., "0", "a", "b"),
+            entry("MessageReceived", 2., "0"),
+            r#"{"DropOutgoing":{"time":1.5,"node":"a"}}"#.to_owned(),
+            sent(1.5, "1", "a", "b"),
+            sent(3., "2", "b", "a"),
+        ];
+        let trace = trace(&log.join("\n"));
+        let mut replay = Replay::new(&trace);
+        for time in [0.5, 1., 1.5, 2., 3., 4.] {
+            let applied = replay.advance(&trace, time.into());
+            assert!(applied.iter().all(|event| event.time <= time.into()));
+            let stepped = replay.state_at(&trace, time.into());
+            let replayed = trace.state_at(time);
+            assert_eq!(stepped.messages, replayed.messages);
+            assert_eq!(stepped.nodes, replayed.nodes);
+        }
+        assert!(replay.peek(&trace).is_none());
+        let dropped = trace.messages.id("1").unwrap();
+        assert_eq!(
+            replay.drop_reason(dropped),
+            Some(DropReason::SrcDropsOutgoing)
+        );
+    }
+
+    #[test]
+    fn state_at_applies_network_rules_on_sending() {
+        let log = [
+            NODES.to_owned(),
+            r#"{"DropOutgoing":{"time":1.0,"node":"a"}}"#.to_owned(),
+            sent(2., "0", "a", "b"),
+            sent(2., "1", "b", "a"),
+            sent(2., "2", "a", "a"),
+            entry("MessageReceived", 3., "1"),
+            entry("MessageReceived", 3., "2"),
+            r#"{"NetworkReset":{"time":4.0}}"#.to_owned(),
+            sent(5., "3", "a", "b"),
+            entry("MessageReceived", 6., "3"),
+        ];
+        let trace = trace(&log.join("\n"));
+        let state = trace.state_at(5.5);
+        let msg_state = |name: &str| state.message_state(trace.messages.id(name).unwrap());
+        let dropped = Some(MessageState::Dropped(DropReason::SrcDropsOutgoing));
+        assert_eq!(msg_state("0"), dropped);
+        assert_eq!(msg_state("1"), Some(MessageState::Delivered));
+        assert_eq!(msg_state("2"), Some(MessageState::Delivered));
+        assert_eq!(msg_state("3"), Some(MessageState::InFlight));
+        assert!(state
+            .message(trace.messages.id("0").unwrap())
+            .unwrap()
+            .copies
+            .is_empty());
+    }
+
+    #[test]
+    fn state_at_keeps_timers_until_they_are_removed() {
+        let timer = |id: &str, time: f64| {
+            format!(
+                r#"{{"TimerSet":{{"time":{time:?},"timer_id":"{id}","timer_name":"t","node":"a","proc":"p","delay":1.0}}}}"#
+            )
+        };
+        let log = [
+            NODES.to_owned(),
+            timer("fired", 1.),
+            r#"{"TimerFired":{"time":2.0,"timer_id":"fired"}}"#.to_owned(),
+            timer("kept", 1.),
+        ];
+        let trace = trace(&log.join("\n"));
+        let (fired, kept) = (
+            trace.timers.id("fired").unwrap(),
+            trace.timers.id("kept").unwrap(),
+        );
+        let a = trace.nodes.id("a").unwrap();
+        assert!(trace.state_at(0.5).node(a).timers.is_empty());
+        assert_eq!(trace.state_at(1.5).node(a).timers, [fired, kept]);
+        // a timer that never fires stays set until the end of the trace
+        assert_eq!(trace.state_at(2.).node(a).timers, [kept]);
+        assert!(!trace.timers[fired].is_active_at(2.0.into()));
+        assert!(trace.timers[kept].is_active_at(100.0.into()));
+        assert!(!trace.timers[kept].is_active_at(0.5.into()));
+    }
+
+    #[test]
+    fn partition_groups_of_both_forms() {
+        let forms = [
+            r#"{"NetworkPartition":{"time":1.0,"groups":[["a"],["b","c"]]}}"#,
+            r#"{"NetworkPartition":{"time":1.0,"group1":["a"],"group2":["b","c"]}}"#,
+        ];
+        for partition in forms {
+            let log = [
+                NODES,
+                r#"{"NodeStarted":{"time":0.0,"node":"c","node_id":2}}"#,
+                partition,
+            ];
+            let trace = trace(&log.join("\n"));
+            let [a, b, c] = ["a", "b", "c"].map(|name| trace.nodes.id(name).unwrap());
+            let network = trace.state_at(1.).network;
+            assert_eq!(network.partition, Some(vec![vec![a], vec![b, c]]));
+            assert_eq!(network.drop_reason(a, c), Some(DropReason::Partition));
+            assert_eq!(network.drop_reason(c, a), Some(DropReason::Partition));
+            assert_eq!(network.drop_reason(b, c), None);
+            assert!(trace.state_at(0.5).network.partition.is_none());
+        }
+    }
+
+    #[test]
+    fn arrivals_without_queueing_are_deliveries() {
+        assert_eq!(message(&[2., 5.], &[]).arrivals(), times(&[2., 5.]));
+    }
+
+    #[test]
+    fn arrivals_match_queued_copies() {
+        assert_eq!(message(&[4.], &[3.]).arrivals(), times(&[3.]));
+        assert_eq!(message(&[4., 6.], &[1., 3.]).arrivals(), times(&[1., 3.]));
+    }
+
+    #[test]
+    fn arrivals_of_mixed_queued_and_unqueued_copies() {
+        // the first copy is handled at once, the second one waits from 3 to 6
+        assert_eq!(message(&[2., 6.], &[3.]).arrivals(), times(&[2., 3.]));
+        assert_eq!(message(&[2., 6.], &[1.]).arrivals(), times(&[1., 6.]));
+    }
+
+    #[test]
+    fn arrivals_keep_queued_copies_that_are_never_delivered() {
+        assert_eq!(message(&[2.], &[1., 4.]).arrivals(), times(&[1., 4.]));
+        assert_eq!(message(&[], &[4.]).arrivals(), times(&[4.]));
+    }
+
+    #[test]
+    fn copies_pair_arrivals_with_deliveries() {
+        let copy = |arrival: f64, delivery: Option<f64>| MessageCopy {
+            arrival: arrival.into(),
+            delivery: delivery.map(SimTime::from),
+        };
+        let copies = message(&[2., 6.], &[3., 7.]).copies();
+        assert_eq!(
+            copies,
+            [copy(2., Some(2.)), copy(3., Some(6.)), copy(7., None)]
+        );
+        assert_eq!(copies[1].state_at(2.5.into()), CopyState::InFlight);
+        assert_eq!(copies[1].state_at(4.0.into()), CopyState::Queued);
+        assert_eq!(copies[1].state_at(6.0.into()), CopyState::Delivered);
+        assert_eq!(copies[2].state_at(10.0.into()), CopyState::Queued);
+    }
+
+    #[test]
+    fn state_of_duplicated_message_copies() {
+        let log = [
+            NODES.to_owned(),
+            sent(0., "0", "a", "b"),
+            entry("MessageReceived", 2., "0"),
+            entry("MessageReceived", 5., "0"),
+        ];
+        let trace = trace(&log.join("\n"));
+        let id = trace.messages.id("0").unwrap();
+        let copies = |time: f64| trace.state_at(time).message(id).unwrap().copies.clone();
+        assert_eq!(
+            trace.state_at(2.5).message_state(id),
+            Some(MessageState::InFlight)
+        );
+        assert_eq!(copies(1.), [CopyState::InFlight, CopyState::InFlight]);
+        assert_eq!(copies(2.5), [CopyState::Delivered, CopyState::InFlight]);
+        assert_eq!(copies(5.), [CopyState::Delivered, CopyState::Delivered]);
+        assert_eq!(
+            trace.state_at(5.).message_state(id),
+            Some(MessageState::Delivered)
+        );
+    }
+
+    #[test]
+    fn state_of_partly_queued_duplicates() {
+        let log = [
+            NODES.to_owned(),
+            sent(0., "0", "a", "b"),
+            entry("MessageReceived", 2., "0"),
+            entry("MessageQueued", 3., "0"),
+            entry("MessageReceived", 6., "0"),
+        ];
+        let trace = trace(&log.join("\n"));
+        let id = trace.messages.id("0").unwrap();
+        assert_eq!(
+            trace.state_at(1.).message_state(id),
+            Some(MessageState::InFlight)
+        );
+        assert_eq!(
+            trace.state_at(2.5).message_state(id),
+            Some(MessageState::InFlight)
+        );
+        assert_eq!(
+            trace.state_at(4.).message_state(id),
+            Some(MessageState::Queued)
+        );
+        assert_eq!(
+            trace.state_at(6.).message_state(id),
+            Some(MessageState::Delivered)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use clap::{Args, Parser, Subcommand};
+
+use crate::visualization::layout::LayoutKind;
+
+/// Replays a dslab execution trace as an animated picture of the system.
+#[derive(Parser, Debug)]
+#[command(version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub view: ViewArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Opens the viewer (the default when no subcommand is given)
+    View(ViewArgs),
+    /// Prints a summary of a trace without opening a window
+    Stats {
+        /// Path to the trace
+        trace: String,
+    },
+    /// Checks that every line of a trace is a valid log entry
+    Validate {
+        /// Path to the trace
+        trace: String,
+    },
+    /// Writes a synthetic gossip trace with many nodes for measuring viewer performance
+    BenchTrace {
+        /// Path to write the trace to
+        output: String,
+
+        /// Number of nodes
+        #[arg(long, default_value_t = 1000)]
+        nodes: usize,
+
+        /// Number of gossip rounds
+        #[arg(long, default_value_t = 10)]
+        rounds: usize,
+
+        /// Messages sent by each node in a round
+        #[arg(long, default_value_t = 3)]
+        fanout: usize,
+
+        /// Probability of a message being dropped
+        #[arg(long, default_value_t = 0.01)]
+        drop_rate: f64,
+
+        /// Seed of the random generator
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ViewArgs {
+    /// Path to the trace
+    #[arg(default_value = "examples/ping-pong.txt")]
+    pub trace: String,
+
+    /// Simulation time to start the replay from
+    #[arg(long, value_name = "TIME", value_parser = parse_non_negative)]
+    pub start_at: Option<f64>,
+
+    /// Playback speed (simulation seconds per real second)
+    #[arg(long, value_parser = parse_non_negative)]
+    pub speed: Option<f64>,
+
+    /// Start with the replay paused
+    #[arg(long)]
+    pub paused: bool,
+
+    /// Show events only for these nodes
+    #[arg(long, value_delimiter = ',', value_name = "NODE,...")]
+    pub nodes: Vec<String>,
+
+    /// Show only messages of these types
+    #[arg(long, value_delimiter = ',', value_name = "TYPE,...")]
+    pub types: Vec<String>,
+
+    /// Node layout
+    #[arg(long, value_enum)]
+    pub layout: Option<LayoutKind>,
+
+    /// Protocol of the trace, message type colors are saved per protocol
+    #[arg(long)]
+    pub protocol: Option<String>,
+
+    /// Additional config file (TOML or JSON)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<String>,
+
+    /// Override a config value, e.g. --set theme.background="#202020"
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub config_overrides: Vec<String>,
+
+    /// Open a window instead of going fullscreen
+    #[arg(long)]
+    pub windowed: bool,
+}
+
+fn parse_non_negative(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if !number.is_finite() => Err("must be a finite number".to_owned()),
+        Ok(number) if number < 0. => Err("must not be negative".to_owned()),
+        Ok(number) => Ok(number),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_negative_numbers_are_finite() {
+        assert_eq!(parse_non_negative("0"), Ok(0.));
+        assert_eq!(parse_non_negative("2.5"), Ok(2.5));
+        assert_eq!(
+            parse_non_negative("-1"),
+            Err("must not be negative".to_owned())
+        );
+        for value in ["nan", "inf", "-inf", "infinity"] {
+            assert_eq!(
+                parse_non_negative(value),
+                Err("must be a finite number".to_owned())
+            );
+        }
+        assert!(parse_non_negative("fast").is_err());
+    }
+}
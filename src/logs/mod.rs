@@ -1 +0,0 @@
-pub mod log_entities;
@@ -1,34 +1,136 @@
-mod logs;
+mod cli;
+mod tools;
 mod visualization;
 
-use std::env;
+use std::{path::Path, process};
 
+use clap::Parser;
+use cli::{Cli, Command, ViewArgs};
+use dslab_trace::Trace;
 use macroquad::prelude::*;
-use visualization::{event_controller::EventController, state::state::State};
+use tools::bench::BenchTraceOptions;
+use visualization::{
+    config::{self, config, Config},
+    state::state::State,
+};
+
+fn exit_with_error(err: String) -> ! {
+    eprintln!("error: {}", err);
+    process::exit(1);
+}
+
+fn load_config(args: &ViewArgs) -> Result<Config, String> {
+    let mut loaded = Config::load(
+        args.config.as_deref().map(Path::new),
+        &args.config_overrides,
+    )?;
+    if args.windowed {
+        loaded.window.fullscreen = false;
+    }
+    if let Some(speed) = args.speed {
+        loaded.speed = Some(speed as f32);
+    }
+    if let Some(layout) = args.layout {
+        loaded.layout = layout;
+    }
+    if let Some(protocol) = &args.protocol {
+        loaded.protocol = Some(protocol.clone());
+    }
+    Ok(loaded)
+}
 
 fn window_conf() -> Conf {
+    let window = &config().window;
     Conf {
         window_title: "Dist sys cartoon".to_owned(),
-        high_dpi: true,
-        fullscreen: true,
+        high_dpi: window.high_dpi,
+        fullscreen: window.fullscreen,
+        window_width: window.width,
+        window_height: window.height,
         ..Default::default()
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
+fn main() {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Some(Command::Stats { trace }) => tools::stats::print_stats(&trace),
+        Some(Command::Validate { trace }) => tools::validate::validate(&trace),
+        Some(Command::BenchTrace {
+            output,
+            nodes,
+            rounds,
+            fanout,
+            drop_rate,
+            seed,
+        }) => tools::bench::generate_bench_trace(
+            &output,
+            &BenchTraceOptions {
+                nodes,
+                rounds,
+                fanout,
+                drop_rate,
+                seed,
+            },
+        ),
+        Some(Command::View(args)) => start_viewer(args),
+        None => start_viewer(cli.view),
+    };
+    if let Err(err) = result {
+        exit_with_error(err);
+    }
+}
+
+/// Checks the node and type filters against the trace, the viewer applies them as they are.
+fn check_filters(trace: &Trace, args: &ViewArgs) -> Result<(), String> {
+    if let Some(node) = args
+        .nodes
+        .iter()
+        .find(|node| trace.nodes.id(node).is_none())
+    {
+        return Err(format!("no node {} in the trace", node));
+    }
+    let has_type = |tip: &String| trace.messages.values().any(|msg| &msg.tip == tip);
+    if let Some(tip) = args.types.iter().find(|tip| !has_type(tip)) {
+        return Err(format!("no messages of type {} in the trace", tip));
+    }
+    Ok(())
+}
+
+/// Loads the trace before the window opens, so that errors are reported without it.
+fn start_viewer(args: ViewArgs) -> Result<(), String> {
+    config::init(load_config(&args)?);
+    let trace = Trace::read(&args.trace)?;
+    check_filters(&trace, &args)?;
+    macroquad::Window::from_config(window_conf(), async move {
+        run_viewer(args, trace).await;
+    });
+    Ok(())
+}
+
+async fn run_viewer(args: ViewArgs, trace: Trace) {
     rand::srand(macroquad::miniquad::date::now() as _);
 
-    let args: Vec<String> = env::args().collect();
+    let mut state = State::new(args.trace.clone());
 
-    let mut state = State::new();
-    let default_history = "examples/broadcast.json".to_string();
+    state.load_trace(trace);
+    state.apply_layout(false);
+    state.restore_layout();
 
-    let mut ec = EventController::new();
-    ec.parse_log(args.get(1).unwrap_or(&default_history));
-    ec.send_commands(&mut state);
+    if !args.nodes.is_empty() {
+        state.show_events_only_for_nodes(&args.nodes);
+    }
+    if !args.types.is_empty() {
+        state.ui_data.msg_type_filter.show_only(&args.types);
+    }
+    if let Some(start_at) = args.start_at {
+        state.seek(start_at.into());
+    }
+    state.paused = args.paused;
 
     loop {
+        clear_background(config().theme.background.0);
+
         state.draw_ui();
 
         egui_macroquad::draw();
@@ -0,0 +1,119 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use dslab_trace::log_entities::{LogEntry, Message};
+use macroquad::rand;
+
+pub struct BenchTraceOptions {
+    pub nodes: usize,
+    pub rounds: usize,
+    pub fanout: usize,
+    pub drop_rate: f64,
+    pub seed: u64,
+}
+
+/// Writes a synthetic gossip trace: in every round each node fires a timer and sends
+/// `fanout` messages to random peers. Used to measure the frame time of the viewer.
+pub fn generate_bench_trace(output: &str, options: &BenchTraceOptions) -> Result<(), String> {
+    if options.nodes < 2 {
+        return Err("a benchmark trace needs at least 2 nodes".to_owned());
+    }
+    rand::srand(options.seed);
+    let node_name = |i: usize| format!("node-{}", i);
+    let mut events: Vec<LogEntry> = Vec::new();
+
+    for i in 0..options.nodes {
+        events.push(LogEntry::NodeStarted {
+            time: 0.,
+            node: node_name(i),
+            node_id: i as u32,
+        });
+    }
+
+    let mut next_msg_id = 0;
+    for round in 0..options.rounds {
+        let round_start = round as f64;
+        for i in 0..options.nodes {
+            let timer_id = format!("{}-{}", node_name(i), round);
+            let fire_time = round_start + rand::gen_range(0.5, 0.9);
+            events.push(LogEntry::TimerSet {
+                time: round_start,
+                timer_id: timer_id.clone(),
+                timer_name: "gossip".to_owned(),
+                node: node_name(i),
+                proc: "gossip".to_owned(),
+                delay: fire_time - round_start,
+            });
+            events.push(LogEntry::TimerFired {
+                time: fire_time,
+                timer_id,
+            });
+            for _ in 0..options.fanout {
+                let mut peer = rand::gen_range(0, options.nodes - 1);
+                if peer >= i {
+                    peer += 1;
+                }
+                let msg_id = next_msg_id.to_string();
+                next_msg_id += 1;
+                events.push(LogEntry::MessageSent {
+                    time: fire_time,
+                    msg_id: msg_id.clone(),
+                    src_node: node_name(i),
+                    src_proc: "gossip".to_owned(),
+                    dest_node: node_name(peer),
+                    dest_proc: "gossip".to_owned(),
+                    msg: Message {
+                        tip: "GOSSIP".to_owned(),
+                        data: format!("{{\"round\": {}}}", round),
+                    },
+                });
+                let delivery_time = fire_time + rand::gen_range(0.1, 1.5);
+                if rand::gen_range(0., 1.) < options.drop_rate {
+                    events.push(LogEntry::MessageDropped {
+                        time: delivery_time,
+                        msg_id,
+                    });
+                } else {
+                    events.push(LogEntry::MessageReceived {
+                        time: delivery_time,
+                        msg_id,
+                    });
+                }
+            }
+        }
+    }
+
+    events.sort_by(|a, b| entry_time(a).total_cmp(&entry_time(b)));
+
+    let file = File::create(output).map_err(|err| format!("{}: {}", output, err))?;
+    let mut writer = BufWriter::new(file);
+    for event in &events {
+        let line = serde_json::to_string(event).unwrap();
+        writeln!(writer, "{}", line).map_err(|err| format!("{}: {}", output, err))?;
+    }
+    writer
+        .flush()
+        .map_err(|err| format!("{}: {}", output, err))?;
+    println!(
+        "Wrote {} events for {} nodes and {} messages to {}",
+        events.len(),
+        options.nodes,
+        next_msg_id,
+        output
+    );
+    Ok(())
+}
+
+fn entry_time(entry: &LogEntry) -> f64 {
+    match entry {
+        LogEntry::NodeStarted { time, .. }
+        | LogEntry::TimerSet { time, .. }
+        | LogEntry::TimerFired { time, .. }
+        | LogEntry::MessageSent { time, .. }
+        | LogEntry::MessageReceived { time, .. }
+        | LogEntry::MessageDropped { time, .. } => *time,
+        _ => 0.,
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use dslab_trace::{log_entities::LogEntry, reader::read_log};
+
+#[derive(Default)]
+struct TypeStats {
+    sent: usize,
+    received: usize,
+    dropped: usize,
+    corrupted: usize,
+}
+
+/// Prints node, message and timer counts of a trace.
+pub fn print_stats(trace: &str) -> Result<(), String> {
+    let events = read_log(trace)?;
+
+    let mut nodes = 0;
+    let mut timers = 0;
+    let mut local_messages = 0;
+    let mut end_time: f64 = 0.;
+    let mut msg_types: BTreeMap<String, String> = BTreeMap::new();
+    let mut stats: BTreeMap<String, TypeStats> = BTreeMap::new();
+
+    for event in &events {
+        match event {
+            LogEntry::NodeStarted { time, .. } => {
+                nodes += 1;
+                end_time = end_time.max(*time);
+            }
+            LogEntry::MessageSent {
+                time, msg_id, msg, ..
+            } => {
+                msg_types.insert(msg_id.clone(), msg.tip.clone());
+                stats.entry(msg.tip.clone()).or_default().sent += 1;
+                end_time = end_time.max(*time);
+            }
+            LogEntry::MessageReceived { time, msg_id } => {
+                if let Some(tip) = msg_types.get(msg_id) {
+                    stats.entry(tip.clone()).or_default().received += 1;
+                }
+                end_time = end_time.max(*time);
+            }
+            LogEntry::MessageDropped { time, msg_id } => {
+                if let Some(tip) = msg_types.get(msg_id) {
+                    stats.entry(tip.clone()).or_default().dropped += 1;
+                }
+                end_time = end_time.max(*time);
+            }
+            LogEntry::MessageCorrupted { time, msg_id, .. } => {
+                if let Some(tip) = msg_types.get(msg_id) {
+                    stats.entry(tip.clone()).or_default().corrupted += 1;
+                }
+                end_time = end_time.max(*time);
+            }
+            LogEntry::LocalMessageSent { time, .. }
+            | LogEntry::LocalMessageReceived { time, .. } => {
+                local_messages += 1;
+                end_time = end_time.max(*time);
+            }
+            LogEntry::TimerSet { time, .. } => {
+                timers += 1;
+                end_time = end_time.max(*time);
+            }
+            _ => {}
+        }
+    }
+
+    println!("Trace: {}", trace);
+    println!("Entries: {}", events.len());
+    println!("Duration: {:.7}", end_time);
+    println!("Nodes: {}", nodes);
+    println!("Timers: {}", timers);
+    println!("Local messages: {}", local_messages);
+    println!("Messages: {}", msg_types.len());
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10}",
+        "Type", "Sent", "Received", "Dropped", "Corrupted"
+    );
+    for (tip, type_stats) in &stats {
+        println!(
+            "{:<24} {:>10} {:>10} {:>10} {:>10}",
+            tip, type_stats.sent, type_stats.received, type_stats.dropped, type_stats.corrupted
+        );
+    }
+    Ok(())
+}
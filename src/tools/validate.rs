@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use dslab_trace::{log_entities::LogEntry, reader::read_log};
+
+/// Checks that the trace parses and that every event refers to known nodes, messages and timers.
+pub fn validate(trace: &str) -> Result<(), String> {
+    let events = read_log(trace)?;
+
+    let mut nodes: HashSet<&str> = HashSet::new();
+    let mut messages: HashSet<&str> = HashSet::new();
+    let mut timers: HashSet<&str> = HashSet::new();
+    let mut last_time = 0.;
+
+    for (i, event) in events.iter().enumerate() {
+        let entry = i + 1;
+        let check_node = |nodes: &HashSet<&str>, node: &str| {
+            if nodes.contains(node) {
+                Ok(())
+            } else {
+                Err(format!("entry {}: unknown node {}", entry, node))
+            }
+        };
+        let time = match event {
+            LogEntry::NodeStarted { time, node, .. } => {
+                nodes.insert(node);
+                *time
+            }
+            LogEntry::MessageSent {
+                time,
+                msg_id,
+                src_node,
+                dest_node,
+                ..
+            } => {
+                check_node(&nodes, src_node)?;
+                check_node(&nodes, dest_node)?;
+                messages.insert(msg_id);
+                *time
+            }
+            LogEntry::MessageReceived { time, msg_id }
+            | LogEntry::MessageDropped { time, msg_id }
+            | LogEntry::MessageQueued { time, msg_id }
+            | LogEntry::MessageCorrupted { time, msg_id, .. } => {
+                if !messages.contains(msg_id.as_str()) {
+                    return Err(format!("entry {}: unknown message {}", entry, msg_id));
+                }
+                *time
+            }
+            LogEntry::TimerSet {
+                time,
+                timer_id,
+                node,
+                ..
+            } => {
+                check_node(&nodes, node)?;
+                timers.insert(timer_id);
+                *time
+            }
+            LogEntry::TimerFired { time, timer_id }
+            | LogEntry::TimerCancelled { time, timer_id } => {
+                if !timers.contains(timer_id.as_str()) {
+                    return Err(format!("entry {}: unknown timer {}", entry, timer_id));
+                }
+                *time
+            }
+            LogEntry::ProcessStarted { time, node, .. }
+            | LogEntry::LocalMessageSent { time, node, .. }
+            | LogEntry::LocalMessageReceived { time, node, .. }
+            | LogEntry::NodeConnected { time, node }
+            | LogEntry::NodeDisconnected { time, node }
+            | LogEntry::NodeCrashed { time, node }
+            | LogEntry::NodeRecovered { time, node }
+            | LogEntry::DropIncoming { time, node }
+            | LogEntry::PassIncoming { time, node }
+            | LogEntry::DropOutgoing { time, node }
+            | LogEntry::PassOutgoing { time, node }
+            | LogEntry::ProcessStateUpdated { time, node, .. } => {
+                check_node(&nodes, node)?;
+                *time
+            }
+            LogEntry::LinkDisabled { time, from, to }
+            | LogEntry::LinkEnabled { time, from, to } => {
+                check_node(&nodes, from)?;
+                check_node(&nodes, to)?;
+                *time
+            }
+            LogEntry::NetworkPartition { time, groups } => {
+                for node in groups.iter().flatten() {
+                    check_node(&nodes, node)?;
+                }
+                *time
+            }
+            LogEntry::NetworkReset { time } => *time,
+            LogEntry::NetworkConfigChanged { time, from, to, .. } => {
+                if from.is_some() != to.is_some() {
+                    return Err(format!(
+                        "entry {}: link config change needs both from and to",
+                        entry
+                    ));
+                }
+                for node in from.iter().chain(to) {
+                    check_node(&nodes, node)?;
+                }
+                *time
+            }
+        };
+        if time < last_time {
+            return Err(format!(
+                "entry {}: time {} goes back from {}",
+                entry, time, last_time
+            ));
+        }
+        last_time = time;
+    }
+
+    println!("{}: {} entries, OK", trace, events.len());
+    Ok(())
+}
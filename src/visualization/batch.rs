@@ -0,0 +1,164 @@
+use std::f32::consts::PI;
+
+use macroquad::{models::Vertex, prelude::*};
+
+use super::utilities::{BATCH_MAX_INDICES, BATCH_MAX_VERTICES};
+
+/// Collects filled shapes into meshes, so that thousands of nodes, messages and timers
+/// are sent to the GPU in a few draw calls instead of one call per shape.
+#[derive(Default)]
+pub struct ShapeBatch {
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+}
+
+impl ShapeBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of circle segments needed for a circle that is `screen_radius` pixels large.
+    pub fn segments_for(screen_radius: f32) -> usize {
+        (screen_radius.sqrt() * 4.).clamp(6., 48.) as usize
+    }
+
+    fn reserve(&mut self, vertices: usize, indices: usize) -> u16 {
+        if self.vertices.len() + vertices > BATCH_MAX_VERTICES
+            || self.indices.len() + indices > BATCH_MAX_INDICES
+        {
+            self.flush();
+        }
+        self.vertices.len() as u16
+    }
+
+    fn vertex(&mut self, pos: Vec2, color: Color) {
+        self.vertices.push(Vertex {
+            position: Vec3::new(pos.x, pos.y, 0.),
+            uv: Vec2::ZERO,
+            color,
+        });
+    }
+
+    pub fn triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: Color) {
+        let first = self.reserve(3, 3);
+        for point in [a, b, c] {
+            self.vertex(point, color);
+        }
+        self.indices.extend([first, first + 1, first + 2]);
+    }
+
+    pub fn line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: Color) {
+        let side = (to - from).normalize_or_zero().perp() * thickness / 2.;
+        let first = self.reserve(4, 6);
+        for point in [from + side, to + side, to - side, from - side] {
+            self.vertex(point, color);
+        }
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    pub fn dashed_line(&mut self, from: Vec2, to: Vec2, thickness: f32, dash: f32, color: Color) {
+        let length = from.distance(to);
+        if length == 0. {
+            return;
+        }
+        let direction = (to - from) / length;
+        let mut start = 0.;
+        while start < length {
+            let end = f32::min(start + dash, length);
+            self.line(
+                from + direction * start,
+                from + direction * end,
+                thickness,
+                color,
+            );
+            start += dash * 2.;
+        }
+    }
+
+    /// Draws a filled triangle with its tip at `tip`, pointing along `direction`.
+    pub fn arrow_head(&mut self, tip: Vec2, direction: Vec2, size: f32, color: Color) {
+        let direction = direction.normalize_or_zero();
+        let back = tip - direction * size;
+        let side = direction.perp() * size * 0.6;
+        self.triangle(tip, back + side, back - side, color);
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, segments: usize, color: Color) {
+        let first = self.reserve(segments + 1, segments * 3);
+        self.vertex(center, color);
+        for i in 0..segments {
+            let angle = 2. * PI * i as f32 / segments as f32;
+            self.vertex(center + Vec2::from_angle(angle) * radius, color);
+        }
+        for i in 0..segments as u16 {
+            let next = (i + 1) % segments as u16;
+            self.indices
+                .extend([first, first + 1 + i, first + 1 + next]);
+        }
+    }
+
+    /// Draws a filled circle sector from `start_angle` to `end_angle` (in radians).
+    pub fn sector(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        segments: usize,
+        color: Color,
+    ) {
+        let steps = Self::steps_for_angle(segments, end_angle - start_angle);
+        let first = self.reserve(steps + 2, steps * 3);
+        self.vertex(center, color);
+        for i in 0..=steps {
+            let angle = start_angle + (end_angle - start_angle) * i as f32 / steps as f32;
+            self.vertex(center + Vec2::from_angle(angle) * radius, color);
+        }
+        for i in 0..steps as u16 {
+            self.indices.extend([first, first + 1 + i, first + 2 + i]);
+        }
+    }
+
+    fn steps_for_angle(segments: usize, angle: f32) -> usize {
+        ((segments as f32 * angle.abs() / (2. * PI)).ceil() as usize).max(1)
+    }
+
+    /// Draws an arc of the given thickness from `start_angle` to `end_angle` (in radians).
+    #[allow(clippy::too_many_arguments)]
+    pub fn arc(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        thickness: f32,
+        start_angle: f32,
+        end_angle: f32,
+        segments: usize,
+        color: Color,
+    ) {
+        let steps = Self::steps_for_angle(segments, end_angle - start_angle);
+        let first = self.reserve((steps + 1) * 2, steps * 6);
+        for i in 0..=steps {
+            let angle = start_angle + (end_angle - start_angle) * i as f32 / steps as f32;
+            let direction = Vec2::from_angle(angle);
+            self.vertex(center + direction * (radius - thickness / 2.), color);
+            self.vertex(center + direction * (radius + thickness / 2.), color);
+        }
+        for i in 0..steps as u16 {
+            let inner = first + 2 * i;
+            self.indices
+                .extend([inner, inner + 1, inner + 3, inner, inner + 3, inner + 2]);
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.indices.is_empty() {
+            return;
+        }
+        draw_mesh(&Mesh {
+            vertices: std::mem::take(&mut self.vertices),
+            indices: std::mem::take(&mut self.indices),
+            texture: None,
+        });
+    }
+}
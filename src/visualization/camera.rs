@@ -0,0 +1,67 @@
+use macroquad::prelude::*;
+
+use super::utilities::{MAX_ZOOM, MIN_ZOOM};
+
+/// Maps world coordinates, where nodes and messages live, to the screen.
+/// `target` is the world point shown at the center of the screen.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub target: Vec2,
+    pub zoom: f32,
+}
+
+impl Camera {
+    pub fn new(target: Vec2) -> Self {
+        Self { target, zoom: 1. }
+    }
+
+    fn screen_center() -> Vec2 {
+        Vec2::new(screen_width() / 2., screen_height() / 2.)
+    }
+
+    pub fn to_camera2d(&self) -> Camera2D {
+        Camera2D {
+            target: self.target,
+            zoom: Vec2::new(
+                2. * self.zoom / screen_width(),
+                -2. * self.zoom / screen_height(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        (point - Self::screen_center()) / self.zoom + self.target
+    }
+
+    /// World area currently visible on the screen.
+    pub fn visible_rect(&self) -> Rect {
+        let size = Vec2::new(screen_width(), screen_height()) / self.zoom;
+        let origin = self.target - size / 2.;
+        Rect::new(origin.x, origin.y, size.x, size.y)
+    }
+
+    /// Changes the zoom keeping the world point under `screen_point` in place.
+    pub fn zoom_at(&mut self, screen_point: Vec2, factor: f32) {
+        let anchor = self.screen_to_world(screen_point);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.target += anchor - self.screen_to_world(screen_point);
+    }
+
+    pub fn pan_by_screen_delta(&mut self, delta: Vec2) {
+        self.target -= delta / self.zoom;
+    }
+
+    /// Shows the whole `rect` with a margin around it.
+    pub fn fit(&mut self, rect: Rect, margin: f32) {
+        self.target = rect.center();
+        let width = f32::max(rect.w + 2. * margin, 1.);
+        let height = f32::max(rect.h + 2. * margin, 1.);
+        self.zoom =
+            f32::min(screen_width() / width, screen_height() / height).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    pub fn activate(&self) {
+        set_camera(&self.to_camera2d());
+    }
+}
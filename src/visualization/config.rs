@@ -0,0 +1,558 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use dslab_trace::time::TimeUnit;
+use macroquad::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use super::{layout::LayoutKind, state::message::Easing, utilities::*};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Returns the viewer settings. Falls back to defaults if `init` was not called.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+pub fn init(config: Config) {
+    CONFIG.set(config).ok();
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub theme: ThemeConfig,
+    pub node_radius: f32,
+    pub message_radius: f32,
+    pub timer_radius: f32,
+    pub circle_radius: f32,
+    pub partitioned_circle_radius: f32,
+    pub timers_max_number: usize,
+    /// Initial playback speed. If not set, it is derived from the first message delay.
+    pub speed: Option<f32>,
+    pub speed_delta: f32,
+    pub message_easing: Easing,
+    /// Units of the simulated time shown in the viewer.
+    pub time_unit: TimeUnit,
+    /// Digits after the point in the shown simulated time.
+    pub time_precision: usize,
+    /// With more messages in flight, they are drawn as flows between nodes instead.
+    pub max_drawn_messages: usize,
+    /// With more nodes, only impaired links are drawn.
+    pub max_full_mesh_nodes: usize,
+    pub scale: f32,
+    pub scale_delta: f32,
+    pub layout: LayoutKind,
+    /// Keep pinned (dragged by the user) nodes in place on network resets and partitions.
+    pub lock_layout: bool,
+    pub type_colors: HashMap<String, HexColor>,
+    /// Protocol of the trace, colors of message types changed in the legend are saved for it.
+    /// If not set, the name of the trace file is used.
+    pub protocol: Option<String>,
+    /// Fields of the process state drawn under each node.
+    pub state_badges: Vec<StateBadge>,
+    pub keybindings: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            theme: ThemeConfig::default(),
+            node_radius: 15.,
+            message_radius: 5.,
+            timer_radius: 8.,
+            circle_radius: 160.,
+            partitioned_circle_radius: 100.,
+            timers_max_number: 9,
+            speed: None,
+            speed_delta: 0.0002,
+            message_easing: Easing::Linear,
+            time_unit: TimeUnit::Seconds,
+            time_precision: 6,
+            max_drawn_messages: 2000,
+            max_full_mesh_nodes: 64,
+            scale: 1.,
+            scale_delta: 0.05,
+            layout: LayoutKind::Circle,
+            lock_layout: false,
+            type_colors: HashMap::new(),
+            protocol: None,
+            state_badges: Vec::new(),
+            keybindings: KeyBindings::default(),
+        }
+    }
+}
+
+/// A field of the process state shown under each node, e.g. the role of a Raft node.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StateBadge {
+    /// JSON pointer to the field, e.g. `/role`.
+    pub pointer: String,
+    /// Colors of the badge for values of the field. Other values use the text color.
+    pub colors: HashMap<String, HexColor>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub fullscreen: bool,
+    pub width: i32,
+    pub height: i32,
+    pub high_dpi: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            fullscreen: true,
+            width: 1280,
+            height: 800,
+            high_dpi: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub background: HexColor,
+    pub text: HexColor,
+    pub node_colors: Vec<HexColor>,
+    pub default_node: HexColor,
+    pub dead_node: HexColor,
+    pub timer: HexColor,
+    pub ready_timer: HexColor,
+    pub cancelled_timer: HexColor,
+    pub partition_line: HexColor,
+    pub link: HexColor,
+    pub disabled_link: HexColor,
+    pub drop_badge: HexColor,
+    pub flow: HexColor,
+    pub corrupted_message: HexColor,
+    pub highlighted_node: HexColor,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            background: HexColor(BLACK),
+            text: HexColor(WHITE),
+            node_colors: [
+                ORANGE, YELLOW, GREEN, SKYBLUE, BLUE, PURPLE, GOLD, LIGHTGRAY, PINK, LIME, VIOLET,
+                WHITE, MAGENTA,
+            ]
+            .into_iter()
+            .map(HexColor)
+            .collect(),
+            default_node: HexColor(YELLOW),
+            dead_node: HexColor(MAROON),
+            timer: HexColor(ORANGE),
+            ready_timer: HexColor(GREEN),
+            cancelled_timer: HexColor(RED),
+            partition_line: HexColor(LIGHTGRAY),
+            link: HexColor(DARKGRAY),
+            disabled_link: HexColor(RED),
+            drop_badge: HexColor(RED),
+            flow: HexColor(Color::new(0.4, 0.75, 1., 0.6)),
+            corrupted_message: HexColor(MAGENTA),
+            highlighted_node: HexColor(GOLD),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub pause: KeyBinding,
+    pub next_event: KeyBinding,
+    pub zoom_in: KeyBinding,
+    pub zoom_out: KeyBinding,
+    pub speed_up: KeyBinding,
+    pub speed_down: KeyBinding,
+    pub fit_to_content: KeyBinding,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            pause: KeyBinding(vec![KeyCode::Space]),
+            next_event: KeyBinding(vec![KeyCode::Right]),
+            zoom_in: KeyBinding(vec![KeyCode::KpAdd, KeyCode::Equal]),
+            zoom_out: KeyBinding(vec![KeyCode::Minus, KeyCode::KpSubtract]),
+            speed_up: KeyBinding(vec![KeyCode::Up]),
+            speed_down: KeyBinding(vec![KeyCode::Down]),
+            fit_to_content: KeyBinding(vec![KeyCode::F]),
+        }
+    }
+}
+
+/// A set of keys that trigger the same action, written as a list of key names in the config.
+#[derive(Clone, Debug)]
+pub struct KeyBinding(pub Vec<KeyCode>);
+
+impl KeyBinding {
+    pub fn is_pressed(&self) -> bool {
+        self.0.iter().any(|key| is_key_pressed(*key))
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.0.iter().any(|key| is_key_down(*key))
+    }
+}
+
+impl Serialize for KeyBinding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names: Vec<String> = self.0.iter().map(|key| format!("{:?}", key)).collect();
+        names.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyBinding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names
+            .iter()
+            .map(|name| {
+                parse_key_code(name)
+                    .ok_or_else(|| serde::de::Error::custom(format!("unknown key \"{}\"", name)))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(KeyBinding)
+    }
+}
+
+/// A color written as "#rrggbb" or "#rrggbbaa" in the config.
+#[derive(Clone, Copy, Debug)]
+pub struct HexColor(pub Color);
+
+impl Serialize for HexColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, a]: [u8; 4] = self.0.into();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        parse_hex_color(&hex)
+            .map(HexColor)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid color \"{}\"", hex)))
+    }
+}
+
+fn parse_hex_color(hex: &str) -> Option<Color> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+        return None;
+    }
+    let mut channels = (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok());
+    let r = channels.next()??;
+    let g = channels.next()??;
+    let b = channels.next()??;
+    let a = channels.next().unwrap_or(Some(255))?;
+    Some(Color::from_rgba(r, g, b, a))
+}
+
+impl Config {
+    /// Builds the config from defaults, the user config dir, the project-local file,
+    /// an explicitly given file and `key.path=value` overrides, in order of increasing priority.
+    /// Unknown keys are errors, so that a mistyped key is not silently ignored.
+    pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<Self, String> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        if let Some(dir) = dirs::config_dir() {
+            files.extend(config_files_in(
+                &dir.join(CONFIG_DIR_NAME),
+                CONFIG_FILE_NAME,
+            ));
+        }
+        files.extend(config_files_in(Path::new("."), LOCAL_CONFIG_FILE_NAME));
+        files.extend(path.map(Path::to_owned));
+        let layers = files
+            .iter()
+            .map(|file| read_config_file(file))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_layers(layers, overrides)
+    }
+
+    /// Merges the layers of settings over the defaults and then the overrides,
+    /// later ones take priority.
+    fn from_layers(layers: Vec<Value>, overrides: &[String]) -> Result<Self, String> {
+        let mut merged = serde_json::to_value(Config::default()).unwrap();
+        for layer in layers {
+            merge_values(&mut merged, layer);
+        }
+        for assignment in overrides {
+            merge_values(&mut merged, parse_override(assignment)?);
+        }
+        serde_json::from_value(merged).map_err(|err| format!("invalid config: {}", err))
+    }
+}
+
+/// Turns `key.path=value` into a nested object. The value is read as TOML,
+/// and taken as a string if it is not valid TOML.
+fn parse_override(assignment: &str) -> Result<Value, String> {
+    let (key, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| format!("override \"{}\" must look like key=value", assignment))?;
+    let value = toml::from_str::<toml::Table>(&format!("v = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .map(|value| serde_json::to_value(value).unwrap())
+        .unwrap_or_else(|| Value::String(value.to_owned()));
+    let mut nested = value;
+    for part in key.rsplit('.') {
+        nested = Value::Object([(part.to_owned(), nested)].into_iter().collect());
+    }
+    Ok(nested)
+}
+
+fn config_files_in(dir: &Path, stem: &str) -> Vec<PathBuf> {
+    ["toml", "json"]
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+fn read_config_file(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("cannot read config {}: {}", path.display(), err))?;
+    let is_json = path.extension().is_some_and(|ext| ext == "json");
+    if is_json {
+        serde_json::from_str(&content)
+            .map_err(|err| format!("cannot parse config {}: {}", path.display(), err))
+    } else {
+        toml::from_str::<toml::Table>(&content)
+            .map(|table| serde_json::to_value(table).unwrap())
+            .map_err(|err| format!("cannot parse config {}: {}", path.display(), err))
+    }
+}
+
+fn merge_values(base: &mut Value, update: Value) {
+    match (base, update) {
+        (Value::Object(base), Value::Object(update)) => {
+            for (key, value) in update {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_values(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, update) => *base = update,
+    }
+}
+
+pub fn parse_key_code(name: &str) -> Option<KeyCode> {
+    let key = match name {
+        "Space" => KeyCode::Space,
+        "Apostrophe" => KeyCode::Apostrophe,
+        "Comma" => KeyCode::Comma,
+        "Minus" => KeyCode::Minus,
+        "Period" => KeyCode::Period,
+        "Slash" => KeyCode::Slash,
+        "Key0" => KeyCode::Key0,
+        "Key1" => KeyCode::Key1,
+        "Key2" => KeyCode::Key2,
+        "Key3" => KeyCode::Key3,
+        "Key4" => KeyCode::Key4,
+        "Key5" => KeyCode::Key5,
+        "Key6" => KeyCode::Key6,
+        "Key7" => KeyCode::Key7,
+        "Key8" => KeyCode::Key8,
+        "Key9" => KeyCode::Key9,
+        "Semicolon" => KeyCode::Semicolon,
+        "Equal" => KeyCode::Equal,
+        "A" => KeyCode::A,
+        "B" => KeyCode::B,
+        "C" => KeyCode::C,
+        "D" => KeyCode::D,
+        "E" => KeyCode::E,
+        "F" => KeyCode::F,
+        "G" => KeyCode::G,
+        "H" => KeyCode::H,
+        "I" => KeyCode::I,
+        "J" => KeyCode::J,
+        "K" => KeyCode::K,
+        "L" => KeyCode::L,
+        "M" => KeyCode::M,
+        "N" => KeyCode::N,
+        "O" => KeyCode::O,
+        "P" => KeyCode::P,
+        "Q" => KeyCode::Q,
+        "R" => KeyCode::R,
+        "S" => KeyCode::S,
+        "T" => KeyCode::T,
+        "U" => KeyCode::U,
+        "V" => KeyCode::V,
+        "W" => KeyCode::W,
+        "X" => KeyCode::X,
+        "Y" => KeyCode::Y,
+        "Z" => KeyCode::Z,
+        "LeftBracket" => KeyCode::LeftBracket,
+        "Backslash" => KeyCode::Backslash,
+        "RightBracket" => KeyCode::RightBracket,
+        "Escape" => KeyCode::Escape,
+        "Enter" => KeyCode::Enter,
+        "Tab" => KeyCode::Tab,
+        "Backspace" => KeyCode::Backspace,
+        "Insert" => KeyCode::Insert,
+        "Delete" => KeyCode::Delete,
+        "Right" => KeyCode::Right,
+        "Left" => KeyCode::Left,
+        "Down" => KeyCode::Down,
+        "Up" => KeyCode::Up,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "F1" => KeyCode::F1,
+        "F2" => KeyCode::F2,
+        "F3" => KeyCode::F3,
+        "F4" => KeyCode::F4,
+        "F5" => KeyCode::F5,
+        "F6" => KeyCode::F6,
+        "F7" => KeyCode::F7,
+        "F8" => KeyCode::F8,
+        "F9" => KeyCode::F9,
+        "F10" => KeyCode::F10,
+        "F11" => KeyCode::F11,
+        "F12" => KeyCode::F12,
+        "Kp0" => KeyCode::Kp0,
+        "Kp1" => KeyCode::Kp1,
+        "Kp2" => KeyCode::Kp2,
+        "Kp3" => KeyCode::Kp3,
+        "Kp4" => KeyCode::Kp4,
+        "Kp5" => KeyCode::Kp5,
+        "Kp6" => KeyCode::Kp6,
+        "Kp7" => KeyCode::Kp7,
+        "Kp8" => KeyCode::Kp8,
+        "Kp9" => KeyCode::Kp9,
+        "KpDecimal" => KeyCode::KpDecimal,
+        "KpDivide" => KeyCode::KpDivide,
+        "KpMultiply" => KeyCode::KpMultiply,
+        "KpSubtract" => KeyCode::KpSubtract,
+        "KpAdd" => KeyCode::KpAdd,
+        "KpEnter" => KeyCode::KpEnter,
+        "KpEqual" => KeyCode::KpEqual,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toml_layer(content: &str) -> Value {
+        serde_json::to_value(toml::from_str::<toml::Table>(content).unwrap()).unwrap()
+    }
+
+    fn overrides(assignments: &[&str]) -> Vec<String> {
+        assignments
+            .iter()
+            .map(|&assignment| assignment.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn config_files_are_parsed_by_extension() {
+        let dir = std::env::temp_dir().join(format!("dslab-config-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let toml_path = dir.join("config.toml");
+        let json_path = dir.join("config.json");
+        fs::write(&toml_path, "node_radius = 20.0\n[window]\nwidth = 640\n").unwrap();
+        fs::write(&json_path, r#"{"window": {"height": 480}}"#).unwrap();
+        let layers = vec![
+            read_config_file(&toml_path).unwrap(),
+            read_config_file(&json_path).unwrap(),
+        ];
+        let broken_path = dir.join("broken.toml");
+        fs::write(&broken_path, "node_radius = ").unwrap();
+        assert!(read_config_file(&broken_path)
+            .unwrap_err()
+            .starts_with("cannot parse config"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let config = Config::from_layers(layers, &[]).unwrap();
+        assert_eq!(config.node_radius, 20.);
+        assert_eq!((config.window.width, config.window.height), (640, 480));
+    }
+
+    #[test]
+    fn layers_change_only_the_given_keys() {
+        let layers = vec![
+            toml_layer("[window]\nwidth = 1024\nfullscreen = false"),
+            toml_layer("[window]\nwidth = 800"),
+        ];
+        let config = Config::from_layers(layers, &[]).unwrap();
+        assert_eq!(config.window.width, 800);
+        assert!(!config.window.fullscreen);
+        assert_eq!(config.window.height, WindowConfig::default().height);
+        assert_eq!(
+            config.timers_max_number,
+            Config::default().timers_max_number
+        );
+    }
+
+    #[test]
+    fn overrides_take_priority_over_layers() {
+        let layers = vec![toml_layer(
+            "message_radius = 3.0\n[window]\nfullscreen = true",
+        )];
+        let config = Config::from_layers(
+            layers,
+            &overrides(&[
+                "window.fullscreen=false",
+                "message_radius=7",
+                "theme.background=\"#202020\"",
+                "protocol=raft",
+            ]),
+        )
+        .unwrap();
+        assert!(!config.window.fullscreen);
+        assert_eq!(config.message_radius, 7.);
+        assert_eq!(config.theme.background.0, Color::from_rgba(32, 32, 32, 255));
+        assert_eq!(config.protocol.as_deref(), Some("raft"));
+    }
+
+    #[test]
+    fn override_without_a_value_is_an_error() {
+        assert_eq!(
+            Config::from_layers(Vec::new(), &overrides(&["window.fullscreen"])).unwrap_err(),
+            "override \"window.fullscreen\" must look like key=value"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_errors() {
+        for assignment in ["node_radiuss=3", "window.fulscreen=false"] {
+            let err = Config::from_layers(Vec::new(), &overrides(&[assignment])).unwrap_err();
+            assert!(err.contains("unknown field"), "{}", err);
+        }
+        let err =
+            Config::from_layers(vec![toml_layer("[themes]\ntext = \"#ffffff\"")], &[]).unwrap_err();
+        assert!(err.contains("unknown field `themes`"), "{}", err);
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        let err = Config::from_layers(Vec::new(), &overrides(&["theme.text=\"#12\""])).unwrap_err();
+        assert!(err.contains("invalid color \"#12\""), "{}", err);
+        let err = Config::from_layers(Vec::new(), &overrides(&["keybindings.pause=[\"Spaced\"]"]))
+            .unwrap_err();
+        assert!(err.contains("unknown key \"Spaced\""), "{}", err);
+    }
+}
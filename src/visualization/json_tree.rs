@@ -0,0 +1,217 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use egui::{CollapsingHeader, Id, Label, Response, Sense, TextEdit, Ui};
+use serde_json::Value;
+
+/// Shows JSON text as a tree of collapsible objects and arrays with a search field.
+/// A right click on an entry copies its JSON pointer or its value.
+/// Text that is not JSON is shown as is.
+pub fn show_json(ui: &mut Ui, id_source: impl Hash + Debug, text: &str) {
+    let id = ui.make_persistent_id(id_source);
+    let value = match parse_cached(ui, id, text) {
+        Some(value) if value.is_object() || value.is_array() => value,
+        Some(value) => {
+            ui.label(scalar_text(&value));
+            return;
+        }
+        None => {
+            ui.label(text);
+            return;
+        }
+    };
+    let search_id = id.with("search");
+    let mut search: String = ui.data().get_temp(search_id).unwrap_or_default();
+    ui.add(TextEdit::singleline(&mut search).hint_text("Search"));
+    let query = search.to_lowercase();
+    ui.data().insert_temp(search_id, search);
+    for (key, path, child) in children(&value, "") {
+        show_entry(ui, id, &key, &path, child, &query);
+    }
+}
+
+/// Parses the text once and keeps the value in egui memory until the text changes.
+fn parse_cached(ui: &Ui, id: Id, text: &str) -> Option<Arc<Value>> {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    let hash = hasher.finish();
+    let parsed_id = id.with("parsed");
+    let cached = ui.data().get_temp::<(u64, Option<Arc<Value>>)>(parsed_id);
+    if let Some((cached_hash, value)) = cached {
+        if cached_hash == hash {
+            return value;
+        }
+    }
+    let value = parse_json(text).map(Arc::new);
+    ui.data().insert_temp(parsed_id, (hash, value.clone()));
+    value
+}
+
+/// Parses JSON text, expanding the strings that hold JSON, see `decode_nested`.
+pub fn parse_json(text: &str) -> Option<Value> {
+    serde_json::from_str(text).ok().map(decode_nested)
+}
+
+/// Replaces strings holding encoded objects or arrays with their values,
+/// as states often keep serialized structures in string fields.
+fn decode_nested(value: Value) -> Value {
+    match value {
+        Value::String(text) => match serde_json::from_str::<Value>(&text) {
+            Ok(inner) if inner.is_object() || inner.is_array() => decode_nested(inner),
+            _ => Value::String(text),
+        },
+        Value::Array(items) => Value::Array(items.into_iter().map(decode_nested).collect()),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, decode_nested(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Keys, JSON pointers and values of the entries of an object or an array.
+fn children<'a>(value: &'a Value, path: &str) -> Vec<(String, String, &'a Value)> {
+    let pointer = |key: &str| format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, child)| (key.clone(), pointer(key), child))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, child)| (i.to_string(), pointer(&i.to_string()), child))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Shows an entry and its children. While searching, only entries that match the query
+/// or contain matching entries are shown, and they are expanded.
+fn show_entry(ui: &mut Ui, id: Id, key: &str, path: &str, value: &Value, query: &str) {
+    if !query.is_empty() && !matches(key, value, query) {
+        return;
+    }
+    // all children of a matching key are shown
+    let query = if key.to_lowercase().contains(query) {
+        ""
+    } else {
+        query
+    };
+    let summary = match value {
+        Value::Object(map) => format!("{{{}}}", count(map.len(), "key")),
+        Value::Array(items) => format!("[{}]", count(items.len(), "item")),
+        _ => {
+            let text = format!("{}: {}", key, scalar_text(value));
+            let response = ui.add(Label::new(text).sense(Sense::click()));
+            add_copy_menu(response, path, value);
+            return;
+        }
+    };
+    let response = CollapsingHeader::new(format!("{}: {}", key, summary))
+        .id_source(id.with(path))
+        .open((!query.is_empty()).then_some(true))
+        .show(ui, |ui| {
+            for (key, path, child) in children(value, path) {
+                show_entry(ui, id, &key, &path, child, query);
+            }
+        });
+    add_copy_menu(response.header_response, path, value);
+}
+
+fn matches(key: &str, value: &Value, query: &str) -> bool {
+    key.to_lowercase().contains(query)
+        || match value {
+            Value::Object(_) | Value::Array(_) => children(value, "")
+                .iter()
+                .any(|(key, _, child)| matches(key, child, query)),
+            _ => scalar_text(value).to_lowercase().contains(query),
+        }
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => format!("\"{}\"", text),
+        value => value.to_string(),
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+fn add_copy_menu(response: Response, path: &str, value: &Value) {
+    response.context_menu(|ui| {
+        if ui.button("Copy path").clicked() {
+            ui.output().copied_text = path.to_owned();
+            ui.close_menu();
+        }
+        if ui.button("Copy value").clicked() {
+            ui.output().copied_text = match value {
+                Value::String(text) => text.clone(),
+                value => serde_json::to_string_pretty(value).unwrap(),
+            };
+            ui.close_menu();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn strings_with_objects_and_arrays_are_decoded() {
+        let text = r#"{"log": "[{\"term\": 1}, \"{\\\"a\\\": 2}\"]", "role": "leader"}"#;
+        assert_eq!(
+            parse_json(text),
+            Some(json!({"log": [{"term": 1}, {"a": 2}], "role": "leader"}))
+        );
+    }
+
+    #[test]
+    fn strings_with_scalars_stay_strings() {
+        let text = r#"{"term": "3", "flag": "true", "name": "\"quoted\"", "empty": ""}"#;
+        assert_eq!(
+            parse_json(text),
+            Some(json!({"term": "3", "flag": "true", "name": "\"quoted\"", "empty": ""}))
+        );
+    }
+
+    #[test]
+    fn invalid_json_is_not_parsed() {
+        assert_eq!(parse_json("{\"a\": "), None);
+        assert_eq!(parse_json(""), None);
+    }
+
+    #[test]
+    fn children_have_escaped_json_pointers() {
+        let value = json!({"a/b": {"c~d": [true]}});
+        let (key, path, child) = children(&value, "").remove(0);
+        assert_eq!((key.as_str(), path.as_str()), ("a/b", "/a~1b"));
+        let (key, path, child) = children(child, &path).remove(0);
+        assert_eq!((key.as_str(), path.as_str()), ("c~d", "/a~1b/c~0d"));
+        let paths: Vec<String> = children(child, &path)
+            .into_iter()
+            .map(|(_, path, _)| path)
+            .collect();
+        assert_eq!(paths, ["/a~1b/c~0d/0"]);
+        assert_eq!(value.pointer("/a~1b/c~0d/0"), Some(&json!(true)));
+        assert!(children(&json!(1), "").is_empty());
+    }
+
+    #[test]
+    fn search_matches_keys_and_values_at_any_depth() {
+        let value = json!({"state": {"role": "Leader", "peers": [1, 2]}});
+        assert!(matches("", &value, "leader"));
+        assert!(matches("", &value, "peers"));
+        assert!(matches("", &value, "2"));
+        assert!(!matches("", &value, "follower"));
+    }
+}
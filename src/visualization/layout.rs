@@ -0,0 +1,324 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    f32::consts::PI,
+};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use dslab_trace::arena::NodeId;
+
+use super::spatial::SpatialGrid;
+
+/// Number of messages sent between each pair of nodes, regardless of direction.
+pub type Traffic = HashMap<(NodeId, NodeId), usize>;
+
+pub fn traffic_between(traffic: &Traffic, a: NodeId, b: NodeId) -> usize {
+    traffic
+        .get(&(a, b))
+        .or_else(|| traffic.get(&(b, a)))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Places nodes inside a screen area. `scale` is the preferred size of the picture,
+/// e.g. the circle radius for the circle layout.
+pub trait Layout {
+    fn place(
+        &self,
+        nodes: &[NodeId],
+        area: Rect,
+        scale: f32,
+        traffic: &Traffic,
+    ) -> HashMap<NodeId, Vec2>;
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum LayoutKind {
+    Circle,
+    Grid,
+    ForceDirected,
+    Hierarchical,
+}
+
+impl LayoutKind {
+    pub const ALL: [LayoutKind; 4] = [
+        LayoutKind::Circle,
+        LayoutKind::Grid,
+        LayoutKind::ForceDirected,
+        LayoutKind::Hierarchical,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutKind::Circle => "Circle",
+            LayoutKind::Grid => "Grid",
+            LayoutKind::ForceDirected => "Force-directed",
+            LayoutKind::Hierarchical => "Hierarchical",
+        }
+    }
+
+    pub fn engine(&self) -> Box<dyn Layout> {
+        match self {
+            LayoutKind::Circle => Box::new(CircleLayout),
+            LayoutKind::Grid => Box::new(GridLayout),
+            LayoutKind::ForceDirected => Box::new(ForceDirectedLayout { iterations: 300 }),
+            LayoutKind::Hierarchical => Box::new(HierarchicalLayout),
+        }
+    }
+}
+
+/// Splits the area into a grid of equal regions, one for each partitioned group.
+pub fn partition_regions(area: Rect, count: usize) -> Vec<Rect> {
+    let cols = (count as f32).sqrt().ceil().max(1.) as usize;
+    let rows = count.div_ceil(cols).max(1);
+    let size = Vec2::new(area.w / cols as f32, area.h / rows as f32);
+    (0..count)
+        .map(|i| {
+            let cell = Vec2::new((i % cols) as f32, (i / cols) as f32);
+            let origin = area.point() + cell * size;
+            Rect::new(origin.x, origin.y, size.x, size.y)
+        })
+        .collect()
+}
+
+fn fit_scale(area: Rect, scale: f32) -> f32 {
+    f32::min(scale, f32::min(area.w, area.h) * 0.4)
+}
+
+pub struct CircleLayout;
+
+impl Layout for CircleLayout {
+    fn place(
+        &self,
+        nodes: &[NodeId],
+        area: Rect,
+        scale: f32,
+        _traffic: &Traffic,
+    ) -> HashMap<NodeId, Vec2> {
+        let radius = fit_scale(area, scale);
+        nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let angle = (2.0 * PI / (nodes.len() as f32)) * (i as f32);
+                (*node, area.center() + Vec2::from_angle(angle) * radius)
+            })
+            .collect()
+    }
+}
+
+pub struct GridLayout;
+
+impl Layout for GridLayout {
+    fn place(
+        &self,
+        nodes: &[NodeId],
+        area: Rect,
+        scale: f32,
+        _traffic: &Traffic,
+    ) -> HashMap<NodeId, Vec2> {
+        let cols = (nodes.len() as f32).sqrt().ceil().max(1.) as usize;
+        let rows = nodes.len().div_ceil(cols);
+        let size = fit_scale(area, scale) * 2.;
+        let step = size / f32::max(1., cols.max(rows) as f32 - 1.);
+        let origin = area.center()
+            - Vec2::new(
+                step * (cols - 1) as f32 / 2.,
+                step * rows.saturating_sub(1) as f32 / 2.,
+            );
+        nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let cell = Vec2::new((i % cols) as f32, (i / cols) as f32);
+                (*node, origin + cell * step)
+            })
+            .collect()
+    }
+}
+
+/// With more nodes, a node is pushed only by the nodes near it, found with a grid.
+const EXACT_REPULSION_NODES: usize = 200;
+
+/// Fruchterman-Reingold layout where nodes that exchange more messages attract each other stronger.
+pub struct ForceDirectedLayout {
+    pub iterations: usize,
+}
+
+impl ForceDirectedLayout {
+    /// Adds the pushes of the nodes away from each other to `shift`. Above
+    /// `EXACT_REPULSION_NODES`, nodes farther than `2k` apart are not pushed, which keeps
+    /// an iteration linear in the number of nodes when they are spread over the area.
+    fn repel(pos: &[Vec2], k: f32, shift: &mut [Vec2]) {
+        if pos.len() <= EXACT_REPULSION_NODES {
+            for i in 0..pos.len() {
+                for j in (i + 1)..pos.len() {
+                    let push = pair_delta(pos, i, j) * k * k;
+                    shift[i] += push;
+                    shift[j] -= push;
+                }
+            }
+            return;
+        }
+        let mut grid = SpatialGrid::new(2. * k);
+        for (i, &node_pos) in pos.iter().enumerate() {
+            grid.insert(i, node_pos);
+        }
+        for i in 0..pos.len() {
+            grid.for_each_within(pos[i], 2. * k, |&j, _, _| {
+                if j > i {
+                    let push = pair_delta(pos, i, j) * k * k;
+                    shift[i] += push;
+                    shift[j] -= push;
+                }
+            });
+        }
+    }
+}
+
+/// Direction from node `j` to node `i` divided by the distance between them. Nodes
+/// at the same point are moved apart in some direction.
+fn pair_delta(pos: &[Vec2], i: usize, j: usize) -> Vec2 {
+    let mut delta = pos[i] - pos[j];
+    if delta.length() < 0.01 {
+        delta = Vec2::new(0.01 * (i as f32 - j as f32), 0.01);
+    }
+    delta / delta.length_squared()
+}
+
+impl Layout for ForceDirectedLayout {
+    fn place(
+        &self,
+        nodes: &[NodeId],
+        area: Rect,
+        scale: f32,
+        traffic: &Traffic,
+    ) -> HashMap<NodeId, Vec2> {
+        let n = nodes.len();
+        if n < 2 {
+            return CircleLayout.place(nodes, area, scale, traffic);
+        }
+        let radius = fit_scale(area, scale);
+        let initial = CircleLayout.place(nodes, area, scale, traffic);
+        let mut pos: Vec<Vec2> = nodes.iter().map(|node| initial[node]).collect();
+
+        let max_traffic = traffic.values().copied().max().unwrap_or(1).max(1) as f32;
+        let index: HashMap<NodeId, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, &node)| (node, i))
+            .collect();
+        // pairs of nodes that exchanged messages, they attract each other
+        let mut edges: BTreeMap<(usize, usize), f32> = BTreeMap::new();
+        for &(a, b) in traffic.keys() {
+            let (Some(&i), Some(&j)) = (index.get(&a), index.get(&b)) else {
+                continue;
+            };
+            if i != j {
+                let weight = traffic_between(traffic, a, b) as f32 / max_traffic;
+                edges.insert((i.min(j), i.max(j)), weight);
+            }
+        }
+
+        let bounds = Rect::new(
+            area.center().x - radius,
+            area.center().y - radius,
+            radius * 2.,
+            radius * 2.,
+        );
+        let k = (bounds.w * bounds.h / n as f32).sqrt() * 0.5;
+        let mut temperature = radius * 0.2;
+        let cooling = temperature / self.iterations as f32;
+
+        for _ in 0..self.iterations {
+            let mut shift = vec![Vec2::ZERO; n];
+            Self::repel(&pos, k, &mut shift);
+            for (&(i, j), &weight) in &edges {
+                let delta = pos[i] - pos[j];
+                let pull = delta * delta.length() / k * (0.2 + weight);
+                shift[i] -= pull;
+                shift[j] += pull;
+            }
+            for i in 0..n {
+                let length = shift[i].length();
+                if length > 0. {
+                    pos[i] += shift[i] / length * f32::min(length, temperature);
+                }
+                pos[i] = pos[i].clamp(bounds.point(), bounds.point() + bounds.size());
+            }
+            temperature = f32::max(temperature - cooling, 0.5);
+        }
+
+        nodes.iter().copied().zip(pos).collect()
+    }
+}
+
+/// Puts the busiest node on top and the rest in layers by their distance to it
+/// in the graph of exchanged messages.
+pub struct HierarchicalLayout;
+
+impl Layout for HierarchicalLayout {
+    fn place(
+        &self,
+        nodes: &[NodeId],
+        area: Rect,
+        scale: f32,
+        traffic: &Traffic,
+    ) -> HashMap<NodeId, Vec2> {
+        if nodes.is_empty() {
+            return HashMap::new();
+        }
+        let total = |node: NodeId| -> usize {
+            nodes
+                .iter()
+                .map(|&other| traffic_between(traffic, node, other))
+                .sum()
+        };
+        let root = (0..nodes.len())
+            .max_by_key(|&i| (total(nodes[i]), usize::MAX - i))
+            .unwrap();
+
+        let mut layer_of: Vec<Option<usize>> = vec![None; nodes.len()];
+        layer_of[root] = Some(0);
+        let mut queue = VecDeque::from([root]);
+        while let Some(i) = queue.pop_front() {
+            for j in 0..nodes.len() {
+                if layer_of[j].is_none() && traffic_between(traffic, nodes[i], nodes[j]) > 0 {
+                    layer_of[j] = Some(layer_of[i].unwrap() + 1);
+                    queue.push_back(j);
+                }
+            }
+        }
+        let reached_layers = layer_of.iter().flatten().max().unwrap() + 1;
+        let mut layers: Vec<Vec<usize>> = vec![Vec::new(); reached_layers];
+        let mut isolated = Vec::new();
+        for (i, layer) in layer_of.iter().enumerate() {
+            match layer {
+                Some(layer) => layers[*layer].push(i),
+                None => isolated.push(i),
+            }
+        }
+        if !isolated.is_empty() {
+            layers.push(isolated);
+        }
+
+        let size = fit_scale(area, scale) * 2.;
+        let layer_step = size / f32::max(1., layers.len() as f32 - 1.);
+        let top = area.center().y - layer_step * (layers.len() - 1) as f32 / 2.;
+        let mut positions = HashMap::new();
+        for (depth, layer) in layers.iter().enumerate() {
+            let step = size / f32::max(1., layer.len() as f32 - 1.);
+            let left = area.center().x - step * (layer.len() - 1) as f32 / 2.;
+            for (k, &i) in layer.iter().enumerate() {
+                positions.insert(
+                    nodes[i],
+                    Vec2::new(left + step * k as f32, top + layer_step * depth as f32),
+                );
+            }
+        }
+        positions
+    }
+}
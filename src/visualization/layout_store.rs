@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::utilities::LAYOUT_FILE_SUFFIX;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedNode {
+    /// Position in world coordinates.
+    pub pos: [f32; 2],
+    pub pinned: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SavedLayout {
+    pub nodes: HashMap<String, SavedNode>,
+}
+
+pub fn layout_path(trace: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", trace, LAYOUT_FILE_SUFFIX))
+}
+
+fn read_layout(path: &Path) -> Option<SavedLayout> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Loads the layout saved for this trace. If there is none, takes the layout of another trace
+/// in the same folder that shares the most node names with this one.
+pub fn load_layout(trace: &str, nodes: &[String]) -> Option<SavedLayout> {
+    if let Some(layout) = read_layout(&layout_path(trace)) {
+        return Some(layout);
+    }
+
+    let dir = Path::new(trace)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(LAYOUT_FILE_SUFFIX)
+        })
+        .filter_map(|entry| read_layout(&entry.path()))
+        .map(|layout| {
+            let common = nodes
+                .iter()
+                .filter(|node| layout.nodes.contains_key(*node))
+                .count();
+            (common, layout)
+        })
+        .filter(|(common, _)| *common > 0)
+        .max_by_key(|(common, _)| *common)
+        .map(|(_, layout)| layout)
+}
+
+pub fn save_layout(trace: &str, layout: &SavedLayout) {
+    let path = layout_path(trace);
+    if let Err(err) = fs::write(&path, serde_json::to_string_pretty(layout).unwrap()) {
+        eprintln!("Failed to save layout to {:?}: {}", path, err);
+    }
+}
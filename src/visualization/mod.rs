@@ -1,3 +1,9 @@
-pub mod event_controller;
+pub mod batch;
+pub mod camera;
+pub mod config;
+pub mod json_tree;
+pub mod layout;
+pub mod layout_store;
+pub mod spatial;
 pub mod state;
 pub mod utilities;
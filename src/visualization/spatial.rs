@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+use super::utilities::calc_dist;
+
+/// Uniform grid over world coordinates for finding the object under the cursor
+/// without checking every node and message.
+pub struct SpatialGrid<K> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(K, Vec2)>>,
+}
+
+impl<K: Clone> SpatialGrid<K> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Removes all objects and changes the cell size, which should be about the size of an object.
+    pub fn reset(&mut self, cell_size: f32) {
+        self.cells.clear();
+        self.cell_size = f32::max(cell_size, 1.);
+    }
+
+    pub fn insert(&mut self, key: K, pos: Vec2) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push((key, pos));
+    }
+
+    /// Calls `f` for the objects that lie within `radius` of `pos`, with their distances to it.
+    pub fn for_each_within<'a>(
+        &'a self,
+        pos: Vec2,
+        radius: f32,
+        mut f: impl FnMut(&'a K, Vec2, f32),
+    ) {
+        let (min_x, min_y) = self.cell(pos - Vec2::splat(radius));
+        let (max_x, max_y) = self.cell(pos + Vec2::splat(radius));
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                let Some(cell) = self.cells.get(&(x, y)) else {
+                    continue;
+                };
+                for (key, object_pos) in cell {
+                    let dist = calc_dist(pos, *object_pos);
+                    if dist < radius {
+                        f(key, *object_pos, dist);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the object closest to `pos` that lies within `radius` of it.
+    pub fn nearest(&self, pos: Vec2, radius: f32) -> Option<K> {
+        let mut best: Option<(f32, &K)> = None;
+        self.for_each_within(pos, radius, |key, _, dist| {
+            if best.is_none_or(|(best_dist, _)| dist < best_dist) {
+                best = Some((dist, key));
+            }
+        });
+        best.map(|(_, key)| key.clone())
+    }
+}
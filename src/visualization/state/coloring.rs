@@ -0,0 +1,342 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use egui::{ComboBox, Context, ScrollArea};
+use macroquad::prelude::*;
+
+use crate::visualization::{config::config, utilities::*};
+use dslab_trace::arena::{Arena, NodeId};
+
+use super::{message::StateMessage, node::StateNode};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColoringMode {
+    SourceNode,
+    DestNode,
+    MsgType,
+    Latency,
+}
+
+impl ColoringMode {
+    pub const ALL: [ColoringMode; 4] = [
+        ColoringMode::SourceNode,
+        ColoringMode::DestNode,
+        ColoringMode::MsgType,
+        ColoringMode::Latency,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColoringMode::SourceNode => "Source node",
+            ColoringMode::DestNode => "Destination node",
+            ColoringMode::MsgType => "Message type",
+            ColoringMode::Latency => "Latency",
+        }
+    }
+}
+
+pub struct MsgColoring {
+    pub mode: ColoringMode,
+    pub protocol: String,
+    pub type_overrides: HashMap<String, Color>,
+    /// Palette slots of the types of the trace, see `assign_type_slots`.
+    pub type_slots: HashMap<String, usize>,
+    pub max_latency: f32,
+    /// Why the last change of the type colors was not saved, shown in the legend.
+    pub save_error: Option<String>,
+}
+
+impl MsgColoring {
+    pub fn new(protocol: String) -> Self {
+        let type_overrides = load_type_colors(&protocol);
+        Self {
+            mode: ColoringMode::SourceNode,
+            protocol,
+            type_overrides,
+            type_slots: HashMap::new(),
+            max_latency: 0.,
+            save_error: None,
+        }
+    }
+
+    pub fn register_latency(&mut self, latency: f32) {
+        self.max_latency = f32::max(self.max_latency, latency);
+    }
+
+    pub fn color_for(&self, msg: &StateMessage, nodes: &Arena<NodeId, StateNode>) -> Color {
+        match self.mode {
+            ColoringMode::SourceNode => nodes[msg.src].color,
+            ColoringMode::DestNode => nodes[msg.dest].color,
+            ColoringMode::MsgType => self.type_color(&msg.tip),
+            ColoringMode::Latency => match msg.latency() {
+                Some(latency) => LATENCY_COLORS[self.latency_bucket(latency as f32)],
+                None => DROPPED_LATENCY_COLOR,
+            },
+        }
+    }
+
+    /// Gives each type a palette slot by hash of its name, taking the next free slot
+    /// on collisions. Types are taken by name, so the slots do not depend on the order
+    /// of the trace, and slots are reused only once the palette is exhausted.
+    pub fn assign_type_slots(&mut self, types: &[String]) {
+        let mut types: Vec<&String> = types.iter().collect();
+        types.sort();
+        let mut taken = [false; MSG_TYPE_COLORS.len()];
+        for tip in types {
+            if taken.iter().all(|&taken| taken) {
+                taken = [false; MSG_TYPE_COLORS.len()];
+            }
+            let mut slot = type_hash_slot(tip);
+            while taken[slot] {
+                slot = (slot + 1) % MSG_TYPE_COLORS.len();
+            }
+            taken[slot] = true;
+            self.type_slots.insert(tip.clone(), slot);
+        }
+    }
+
+    /// Types overridden by the user or in the config keep their colors. Other types get
+    /// the palette color of their slot from `assign_type_slots`, types outside the trace
+    /// get the slot of their hash.
+    pub fn type_color(&self, tip: &str) -> Color {
+        if let Some(color) = self.type_overrides.get(tip) {
+            return *color;
+        }
+        if let Some(color) = config().type_colors.get(tip) {
+            return color.0;
+        }
+        let slot = self
+            .type_slots
+            .get(tip)
+            .copied()
+            .unwrap_or_else(|| type_hash_slot(tip));
+        MSG_TYPE_COLORS[slot]
+    }
+
+    pub fn latency_bucket(&self, latency: f32) -> usize {
+        if self.max_latency <= 0. {
+            return 0;
+        }
+        let bucket = (latency / self.max_latency * LATENCY_COLORS.len() as f32) as usize;
+        bucket.min(LATENCY_COLORS.len() - 1)
+    }
+
+    pub fn draw_ui_mode_selector(&mut self, ui: &mut egui::Ui) {
+        ComboBox::from_label("Message color")
+            .selected_text(self.mode.name())
+            .show_ui(ui, |ui| {
+                for mode in ColoringMode::ALL {
+                    ui.selectable_value(&mut self.mode, mode, mode.name());
+                }
+            });
+    }
+
+    pub fn draw_ui_legend(
+        &mut self,
+        egui_ctx: &Context,
+        show_window: &mut bool,
+        nodes: &[(String, Color)],
+        types: &[String],
+    ) {
+        egui::Window::new("Legend")
+            .open(show_window)
+            .show(egui_ctx, |ui| {
+                self.draw_ui_mode_selector(ui);
+                ui.separator();
+                ui.set_max_height(screen_height() * 0.4);
+                ScrollArea::vertical().show(ui, |ui| match self.mode {
+                    ColoringMode::SourceNode | ColoringMode::DestNode => {
+                        for (node, color) in nodes {
+                            ui.horizontal(|ui| {
+                                draw_ui_color_box(ui, *color);
+                                ui.label(format!("Node {}", node));
+                            });
+                        }
+                    }
+                    ColoringMode::MsgType => {
+                        let mut changed = false;
+                        for tip in types {
+                            ui.horizontal(|ui| {
+                                let color = self.type_color(tip);
+                                let mut rgb = [color.r, color.g, color.b];
+                                if ui.color_edit_button_rgb(&mut rgb).changed() {
+                                    self.type_overrides.insert(
+                                        tip.clone(),
+                                        Color::new(rgb[0], rgb[1], rgb[2], 1.),
+                                    );
+                                    changed = true;
+                                }
+                                ui.label(tip);
+                                if self.type_overrides.contains_key(tip)
+                                    && ui.small_button("Reset").clicked()
+                                {
+                                    self.type_overrides.remove(tip);
+                                    changed = true;
+                                }
+                            });
+                        }
+                        if changed {
+                            self.save_error =
+                                save_type_colors(&self.protocol, &self.type_overrides).err();
+                        }
+                        if let Some(err) = &self.save_error {
+                            ui.colored_label(egui::Color32::RED, err);
+                        }
+                    }
+                    ColoringMode::Latency => {
+                        let step = self.max_latency / LATENCY_COLORS.len() as f32;
+                        for (i, color) in LATENCY_COLORS.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                draw_ui_color_box(ui, *color);
+                                ui.label(format!(
+                                    "{} - {}",
+                                    format_duration((step * i as f32) as f64),
+                                    format_duration((step * (i + 1) as f32) as f64)
+                                ));
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            draw_ui_color_box(ui, DROPPED_LATENCY_COLOR);
+                            ui.label("Dropped");
+                        });
+                    }
+                });
+                ui.set_max_height(f32::INFINITY);
+            });
+    }
+}
+
+fn type_hash_slot(tip: &str) -> usize {
+    let hash = tip.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    (hash % MSG_TYPE_COLORS.len() as u64) as usize
+}
+
+fn draw_ui_color_box(ui: &mut egui::Ui, color: Color) {
+    egui::widgets::color_picker::show_color(ui, egui_color(color), egui::Vec2::new(16., 16.));
+}
+
+fn type_colors_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(TYPE_COLORS_FILE_NAME))
+}
+
+fn read_type_colors_file() -> HashMap<String, HashMap<String, [f32; 3]>> {
+    type_colors_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn load_type_colors(protocol: &str) -> HashMap<String, Color> {
+    read_type_colors_file()
+        .remove(protocol)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(tip, rgb)| (tip, Color::new(rgb[0], rgb[1], rgb[2], 1.)))
+        .collect()
+}
+
+fn save_type_colors(protocol: &str, type_colors: &HashMap<String, Color>) -> Result<(), String> {
+    let Some(path) = type_colors_path() else {
+        return Err("Failed to save message type colors: no config directory".to_owned());
+    };
+    let mut all_colors = read_type_colors_file();
+    all_colors.insert(
+        protocol.to_owned(),
+        type_colors
+            .iter()
+            .map(|(tip, color)| (tip.clone(), [color.r, color.g, color.b]))
+            .collect(),
+    );
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, serde_json::to_string_pretty(&all_colors).unwrap()))
+        .map_err(|err| format!("Failed to save message type colors to {:?}: {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coloring() -> MsgColoring {
+        MsgColoring {
+            mode: ColoringMode::MsgType,
+            protocol: String::new(),
+            type_overrides: HashMap::new(),
+            type_slots: HashMap::new(),
+            max_latency: 0.,
+            save_error: None,
+        }
+    }
+
+    /// Names of message types whose hashes fall into the same slot.
+    fn colliding_types(count: usize) -> Vec<String> {
+        let mut by_slot: HashMap<usize, Vec<String>> = HashMap::new();
+        for i in 0.. {
+            let tip = format!("Type{}", i);
+            let types = by_slot.entry(type_hash_slot(&tip)).or_default();
+            types.push(tip);
+            if types.len() == count {
+                return types.clone();
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn colliding_types_take_the_next_free_slots() {
+        let types = colliding_types(3);
+        let slot = type_hash_slot(&types[0]);
+        let mut coloring = coloring();
+        coloring.assign_type_slots(&types);
+        let mut sorted = types.clone();
+        sorted.sort();
+        for (i, tip) in sorted.iter().enumerate() {
+            assert_eq!(coloring.type_slots[tip], (slot + i) % MSG_TYPE_COLORS.len());
+        }
+    }
+
+    #[test]
+    fn slots_do_not_depend_on_the_order_of_types() {
+        let mut types = colliding_types(2);
+        types.extend(["Ping", "Pong", "Ack"].map(String::from));
+        let mut first = coloring();
+        first.assign_type_slots(&types);
+        types.reverse();
+        let mut second = coloring();
+        second.assign_type_slots(&types);
+        assert_eq!(first.type_slots, second.type_slots);
+    }
+
+    #[test]
+    fn slots_are_reused_only_when_the_palette_is_exhausted() {
+        let types: Vec<String> = (0..MSG_TYPE_COLORS.len() + 1)
+            .map(|i| format!("Type{}", i))
+            .collect();
+        let mut coloring = coloring();
+        coloring.assign_type_slots(&types);
+        let mut sorted = types.clone();
+        sorted.sort();
+        let (palette, rest) = sorted.split_at(MSG_TYPE_COLORS.len());
+        let mut slots: Vec<usize> = palette.iter().map(|tip| coloring.type_slots[tip]).collect();
+        slots.sort();
+        assert_eq!(slots, (0..MSG_TYPE_COLORS.len()).collect::<Vec<_>>());
+        assert_eq!(coloring.type_slots[&rest[0]], type_hash_slot(&rest[0]));
+    }
+
+    #[test]
+    fn overridden_types_keep_their_colors() {
+        let mut coloring = coloring();
+        coloring.assign_type_slots(&["Ping".to_owned()]);
+        assert_eq!(
+            coloring.type_color("Ping"),
+            MSG_TYPE_COLORS[coloring.type_slots["Ping"]]
+        );
+        assert_eq!(
+            coloring.type_color("Unknown"),
+            MSG_TYPE_COLORS[type_hash_slot("Unknown")]
+        );
+        coloring.type_overrides.insert("Ping".to_owned(), WHITE);
+        assert_eq!(coloring.type_color("Ping"), WHITE);
+    }
+}
@@ -0,0 +1,120 @@
+use dslab_trace::{
+    arena::{MessageId, NodeId},
+    trace::EventKind,
+    SimTime,
+};
+
+use super::state::State;
+use crate::visualization::utilities::format_network_params;
+
+/// A line of the Events window. Lines about messages open the message window on click.
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    pub time: SimTime,
+    pub text: String,
+    pub msg: Option<MessageId>,
+    /// Nodes the line is about besides the message ones, the line is hidden with any of them.
+    pub nodes: Vec<NodeId>,
+}
+
+impl LoggedEvent {
+    pub fn new(time: SimTime, text: String, msg: Option<MessageId>) -> Self {
+        Self {
+            time,
+            text,
+            msg,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Describes a trace event that the replay applied.
+    pub fn from_event(time: SimTime, event: &EventKind, state: &State) -> Self {
+        let node = |id: &NodeId| state.nodes.name(*id);
+        let text = match event {
+            EventKind::NodeStarted(id) => format!("node {} started", node(id)),
+            EventKind::MessageSent(id) => {
+                let msg = &state.messages[*id];
+                let mut text = format!(
+                    "message {} ({}) sent {} -> {}",
+                    state.messages.name(*id),
+                    msg.tip,
+                    node(&msg.src),
+                    node(&msg.dest)
+                );
+                if let Some(reason) = state.replay.drop_reason(*id) {
+                    text += &format!(", dropped: {}", reason.description());
+                }
+                return Self::new(time, text, Some(*id));
+            }
+            EventKind::LocalMessageSent(id) => {
+                let msg = &state.local_messages[*id];
+                format!("local message ({}) sent by {}", msg.tip, node(&msg.node))
+            }
+            EventKind::LocalMessageReceived(id) => {
+                let msg = &state.local_messages[*id];
+                format!(
+                    "local message ({}) received by {}",
+                    msg.tip,
+                    node(&msg.node)
+                )
+            }
+            EventKind::NodeConnected(id) => format!("node {} connected", node(id)),
+            EventKind::NodeDisconnected(id) => format!("node {} disconnected", node(id)),
+            EventKind::TimerSet(id) => {
+                let timer = &state.timers[*id].timer;
+                format!("timer {} set on {}", timer.name, node(&timer.node))
+            }
+            EventKind::LinkDisabled(from, to) => {
+                format!("link {} -> {} disabled", node(from), node(to))
+            }
+            EventKind::LinkEnabled(from, to) => {
+                format!("link {} -> {} enabled", node(from), node(to))
+            }
+            EventKind::DropIncoming(id) => format!("{} drops incoming", node(id)),
+            EventKind::PassIncoming(id) => format!("{} passes incoming", node(id)),
+            EventKind::DropOutgoing(id) => format!("{} drops outgoing", node(id)),
+            EventKind::PassOutgoing(id) => format!("{} passes outgoing", node(id)),
+            EventKind::NetworkPartition(groups) => {
+                let groups: Vec<String> = groups
+                    .iter()
+                    .map(|group| format!("{:?}", state.node_names(group)))
+                    .collect();
+                format!("network partition {}", groups.join(" -x- "))
+            }
+            EventKind::NetworkReset => "network reset".to_owned(),
+            EventKind::NetworkConfigChanged(link, params) => {
+                let target = match link {
+                    Some((from, to)) => format!("link {} -> {}", node(from), node(to)),
+                    None => "network".to_owned(),
+                };
+                format!("{} config: {}", target, format_network_params(params))
+            }
+            EventKind::NodeStateUpdated(id, _) => format!("state of {} updated", node(id)),
+        };
+        let nodes = match event {
+            EventKind::NodeStarted(id)
+            | EventKind::NodeConnected(id)
+            | EventKind::NodeDisconnected(id)
+            | EventKind::DropIncoming(id)
+            | EventKind::PassIncoming(id)
+            | EventKind::DropOutgoing(id)
+            | EventKind::PassOutgoing(id)
+            | EventKind::NodeStateUpdated(id, _) => vec![*id],
+            EventKind::LocalMessageSent(id) | EventKind::LocalMessageReceived(id) => {
+                vec![state.local_messages[*id].node]
+            }
+            EventKind::TimerSet(id) => vec![state.timers[*id].timer.node],
+            EventKind::LinkDisabled(from, to) | EventKind::LinkEnabled(from, to) => {
+                vec![*from, *to]
+            }
+            EventKind::NetworkConfigChanged(link, _) => {
+                link.iter().flat_map(|&(from, to)| [from, to]).collect()
+            }
+            _ => Vec::new(),
+        };
+        Self {
+            nodes,
+            ..Self::new(time, text, None)
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::{BTreeMap, HashMap};
+
+use egui::{
+    plot::{Bar, BarChart, Plot},
+    ComboBox, Context, Grid, ScrollArea, Sense,
+};
+use macroquad::prelude::*;
+
+use crate::visualization::{config::config, utilities::*};
+use dslab_trace::arena::{Arena, MessageId, NodeId};
+
+use super::{
+    message::StateMessage,
+    node::StateNode,
+    state::{HiddenByFilters, UiAction},
+};
+
+const HISTOGRAM_BINS: usize = 20;
+const MAX_OUTLIERS: usize = 20;
+/// With more nodes the link matrix is not drawn.
+const MAX_MATRIX_NODES: usize = 40;
+const MATRIX_CELL_SIZE: f32 = 14.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LatencyGrouping {
+    MsgType,
+    Link,
+}
+
+impl LatencyGrouping {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LatencyGrouping::MsgType => "Message type",
+            LatencyGrouping::Link => "Link",
+        }
+    }
+}
+
+/// Latency of one delivered copy of a message.
+struct Sample {
+    msg: MessageId,
+    link: (NodeId, NodeId),
+    latency: f64,
+}
+
+/// Samples of a message type or a link, sorted by latency.
+struct Group {
+    name: String,
+    /// Sender and receiver of a link group.
+    link: Option<(NodeId, NodeId)>,
+    samples: Vec<usize>,
+}
+
+/// Latencies of delivered messages for the Latency window. Messages a node sends
+/// to itself do not cross the network and are not counted, nor are the messages
+/// hidden by the filters.
+pub struct LatencyStats {
+    pub grouping: LatencyGrouping,
+    /// Group shown in the histogram and the outliers, all samples if not set.
+    pub selected: Option<usize>,
+    samples: Vec<Sample>,
+    by_type: Vec<Group>,
+    by_link: Vec<Group>,
+    all: Group,
+    /// Mean latency of each link.
+    link_means: HashMap<(NodeId, NodeId), f64>,
+    /// Filters the samples were collected for, nothing is collected before the first update.
+    hidden: Option<HiddenByFilters>,
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self {
+            grouping: LatencyGrouping::MsgType,
+            selected: None,
+            samples: Vec::new(),
+            by_type: Vec::new(),
+            by_link: Vec::new(),
+            all: Group {
+                name: "All".to_owned(),
+                link: None,
+                samples: Vec::new(),
+            },
+            link_means: HashMap::new(),
+            hidden: None,
+        }
+    }
+}
+
+impl LatencyStats {
+    /// Collects the samples of the shown messages again if the filters changed.
+    pub fn update(
+        &mut self,
+        messages: &Arena<MessageId, StateMessage>,
+        nodes: &Arena<NodeId, StateNode>,
+        hidden: HiddenByFilters,
+        show: impl Fn(&StateMessage) -> bool,
+    ) {
+        if self.hidden.as_ref() == Some(&hidden) {
+            return;
+        }
+        self.hidden = Some(hidden);
+        self.samples = messages
+            .iter()
+            .filter(|(_, msg)| msg.src != msg.dest && show(msg))
+            .flat_map(|(id, msg)| {
+                msg.deliveries.iter().map(move |&time| Sample {
+                    msg: id,
+                    link: (msg.src, msg.dest),
+                    latency: time - msg.time_sent,
+                })
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..self.samples.len()).collect();
+        order.sort_by(|&a, &b| self.samples[a].latency.total_cmp(&self.samples[b].latency));
+
+        let mut by_type: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        let mut by_link: BTreeMap<(NodeId, NodeId), Vec<usize>> = BTreeMap::new();
+        for &i in &order {
+            let sample = &self.samples[i];
+            by_type
+                .entry(&messages[sample.msg].tip)
+                .or_default()
+                .push(i);
+            by_link.entry(sample.link).or_default().push(i);
+        }
+        self.by_type = by_type
+            .into_iter()
+            .map(|(tip, samples)| Group {
+                name: tip.to_owned(),
+                link: None,
+                samples,
+            })
+            .collect();
+        self.link_means = by_link
+            .iter()
+            .map(|(&link, samples)| (link, self.mean(samples)))
+            .collect();
+        self.by_link = by_link
+            .into_iter()
+            .map(|((from, to), samples)| Group {
+                name: format!("{} -> {}", nodes.name(from), nodes.name(to)),
+                link: Some((from, to)),
+                samples,
+            })
+            .collect();
+        self.all.samples = order;
+        self.selected = None;
+    }
+
+    fn mean(&self, samples: &[usize]) -> f64 {
+        let sum: f64 = samples.iter().map(|&i| self.samples[i].latency).sum();
+        sum / samples.len().max(1) as f64
+    }
+
+    /// Latency below which the given share of the sorted samples lies.
+    fn percentile(&self, samples: &[usize], share: f64) -> f64 {
+        if samples.is_empty() {
+            return 0.;
+        }
+        let rank = ((samples.len() as f64 * share).ceil() as usize).clamp(1, samples.len());
+        self.samples[samples[rank - 1]].latency
+    }
+
+    fn groups(&self) -> &[Group] {
+        match self.grouping {
+            LatencyGrouping::MsgType => &self.by_type,
+            LatencyGrouping::Link => &self.by_link,
+        }
+    }
+
+    fn selected_group(&self) -> &Group {
+        self.selected
+            .and_then(|i| self.groups().get(i))
+            .unwrap_or(&self.all)
+    }
+
+    /// Samples above the upper Tukey fence of the group, the slowest first.
+    fn outliers(&self, group: &Group) -> Vec<usize> {
+        let q1 = self.percentile(&group.samples, 0.25);
+        let q3 = self.percentile(&group.samples, 0.75);
+        let fence = q3 + 1.5 * (q3 - q1);
+        group
+            .samples
+            .iter()
+            .rev()
+            .copied()
+            .take_while(|&i| self.samples[i].latency > fence)
+            .take(MAX_OUTLIERS)
+            .collect()
+    }
+
+    /// Shows the window and returns the actions of the clicked outliers.
+    pub fn draw_ui(
+        &mut self,
+        egui_ctx: &Context,
+        show_window: &mut bool,
+        messages: &Arena<MessageId, StateMessage>,
+        nodes: &Arena<NodeId, StateNode>,
+    ) -> Vec<UiAction> {
+        let mut actions = Vec::new();
+        egui::Window::new("Latency")
+            .open(show_window)
+            .show(egui_ctx, |ui| {
+                if self.samples.is_empty() {
+                    ui.label("No delivered messages among the shown ones");
+                    return;
+                }
+                let grouping = self.grouping;
+                ComboBox::from_label("Group by")
+                    .selected_text(self.grouping.name())
+                    .show_ui(ui, |ui| {
+                        for grouping in [LatencyGrouping::MsgType, LatencyGrouping::Link] {
+                            ui.selectable_value(&mut self.grouping, grouping, grouping.name());
+                        }
+                    });
+                if self.grouping != grouping {
+                    self.selected = None;
+                }
+                self.draw_ui_percentiles(ui);
+                ui.separator();
+                let group = self.selected_group();
+                ui.strong(format!("{}: {} messages", group.name, group.samples.len()));
+                self.draw_ui_histogram(ui, group);
+                ui.collapsing("Outliers", |ui| {
+                    for i in self.outliers(group) {
+                        let sample = &self.samples[i];
+                        let text = format!(
+                            "Message {} ({}): {}",
+                            messages.name(sample.msg),
+                            messages[sample.msg].tip,
+                            format_duration(sample.latency)
+                        );
+                        UiAction::OpenMessage(sample.msg).link(ui, text, &mut actions);
+                    }
+                });
+                ui.collapsing("Link matrix", |ui| self.draw_ui_matrix(ui, nodes));
+            });
+        actions
+    }
+
+    /// Percentiles of each group. Clicking a group selects it for the histogram.
+    fn draw_ui_percentiles(&mut self, ui: &mut egui::Ui) {
+        let mut selected = self.selected;
+        ScrollArea::vertical()
+            .id_source("latency groups")
+            .max_height(screen_height() * 0.25)
+            .show(ui, |ui| {
+                Grid::new("latency percentiles")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for title in ["", "Count", "p50", "p90", "p99", "Max"] {
+                            ui.strong(title);
+                        }
+                        ui.end_row();
+                        let rows = std::iter::once((None, &self.all))
+                            .chain(self.groups().iter().enumerate().map(|(i, g)| (Some(i), g)));
+                        for (i, group) in rows {
+                            if ui.selectable_label(selected == i, &group.name).clicked() {
+                                selected = i;
+                            }
+                            ui.label(group.samples.len().to_string());
+                            for share in [0.5, 0.9, 0.99, 1.] {
+                                let latency = self.percentile(&group.samples, share);
+                                ui.label(format_duration(latency));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        self.selected = selected;
+    }
+
+    fn draw_ui_histogram(&self, ui: &mut egui::Ui, group: &Group) {
+        let unit = config().time_unit.per_second();
+        let min = self.percentile(&group.samples, 0.) * unit;
+        let max = self.percentile(&group.samples, 1.) * unit;
+        let width = ((max - min) / HISTOGRAM_BINS as f64).max(f64::EPSILON);
+        let mut counts = [0; HISTOGRAM_BINS];
+        for &i in &group.samples {
+            let bin = ((self.samples[i].latency * unit - min) / width) as usize;
+            counts[bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+        let bars = counts
+            .iter()
+            .enumerate()
+            .map(|(bin, &count)| {
+                Bar::new(min + (bin as f64 + 0.5) * width, count as f64).width(width)
+            })
+            .collect();
+        Plot::new("latency histogram")
+            .height(screen_height() * 0.2)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .allow_boxed_zoom(false)
+            .show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars)));
+    }
+
+    /// Mean latency of every link between the shown nodes as a colored cell, rows are
+    /// senders and columns receivers. Columns are numbered as the rows. Clicking a cell
+    /// selects the link.
+    fn draw_ui_matrix(&mut self, ui: &mut egui::Ui, nodes: &Arena<NodeId, StateNode>) {
+        let hidden = self.hidden.as_ref().map_or(&[][..], |hidden| &hidden.nodes);
+        let ids: Vec<NodeId> = nodes.ids().filter(|id| !hidden.contains(id)).collect();
+        if ids.len() > MAX_MATRIX_NODES {
+            ui.label(format!("Too many nodes, at most {}", MAX_MATRIX_NODES));
+            return;
+        }
+        let max_mean = self.link_means.values().copied().fold(0., f64::max);
+        let mut clicked = None;
+        Grid::new("latency matrix")
+            .spacing(egui::Vec2::new(1., 1.))
+            .show(ui, |ui| {
+                ui.small("from \\ to");
+                for number in 1..=ids.len() {
+                    ui.small(number.to_string());
+                }
+                ui.end_row();
+                for (number, &from) in ids.iter().enumerate() {
+                    ui.small(format!("{} {}", number + 1, nodes.name(from)));
+                    for &to in &ids {
+                        let size = egui::Vec2::splat(MATRIX_CELL_SIZE);
+                        let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+                        let Some(&mean) = self.link_means.get(&(from, to)) else {
+                            ui.painter()
+                                .rect_filled(rect, 0., egui::Color32::from_gray(40));
+                            continue;
+                        };
+                        let share = if max_mean > 0. { mean / max_mean } else { 0. };
+                        let index = (share * (LATENCY_COLORS.len() - 1) as f64).round() as usize;
+                        ui.painter()
+                            .rect_filled(rect, 0., egui_color(LATENCY_COLORS[index]));
+                        let response = response.on_hover_text(format!(
+                            "{} -> {}: {}",
+                            nodes.name(from),
+                            nodes.name(to),
+                            format_duration(mean)
+                        ));
+                        if response.clicked() {
+                            clicked = Some((from, to));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(link) = clicked {
+            self.grouping = LatencyGrouping::Link;
+            self.selected = self
+                .by_link
+                .iter()
+                .position(|group| group.link == Some(link));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dslab_trace::{arena::ArenaId, SimTime};
+
+    /// Stats of a single link, with the samples sorted by latency as `update` sorts them.
+    fn link_stats(latencies: &[f64]) -> (LatencyStats, Group) {
+        let samples = latencies
+            .iter()
+            .enumerate()
+            .map(|(i, &latency)| Sample {
+                msg: MessageId::from_index(i),
+                link: (NodeId::from_index(0), NodeId::from_index(1)),
+                latency,
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..latencies.len()).collect();
+        order.sort_by(|&a, &b| latencies[a].total_cmp(&latencies[b]));
+        let group = Group {
+            name: "0 -> 1".to_owned(),
+            link: Some((NodeId::from_index(0), NodeId::from_index(1))),
+            samples: order,
+        };
+        let stats = LatencyStats {
+            samples,
+            ..LatencyStats::default()
+        };
+        (stats, group)
+    }
+
+    #[test]
+    fn percentiles_are_sample_latencies() {
+        let (stats, group) = link_stats(&[4., 1., 3., 2.]);
+        assert_eq!(stats.percentile(&group.samples, 0.), 1.);
+        assert_eq!(stats.percentile(&group.samples, 0.5), 2.);
+        assert_eq!(stats.percentile(&group.samples, 0.9), 4.);
+        assert_eq!(stats.percentile(&group.samples, 1.), 4.);
+        assert_eq!(stats.mean(&group.samples), 2.5);
+    }
+
+    #[test]
+    fn single_sample_is_every_percentile_and_no_outlier() {
+        let (stats, group) = link_stats(&[0.7]);
+        for share in [0., 0.25, 0.5, 0.99, 1.] {
+            assert_eq!(stats.percentile(&group.samples, share), 0.7);
+        }
+        assert!(stats.outliers(&group).is_empty());
+    }
+
+    #[test]
+    fn no_samples() {
+        let (stats, group) = link_stats(&[]);
+        assert_eq!(stats.percentile(&group.samples, 0.5), 0.);
+        assert_eq!(stats.mean(&group.samples), 0.);
+        assert!(stats.outliers(&group).is_empty());
+    }
+
+    #[test]
+    fn outliers_are_above_the_upper_fence_slowest_first() {
+        let (stats, group) = link_stats(&[1., 12., 1., 1., 10., 1., 1., 1.]);
+        assert_eq!(stats.outliers(&group), vec![1, 4]);
+        let (stats, group) = link_stats(&[1., 2., 3., 4.]);
+        assert!(stats.outliers(&group).is_empty());
+    }
+
+    #[test]
+    fn update_skips_messages_to_self_and_hidden_messages() {
+        let mut nodes = Arena::new();
+        for name in ["a", "b"] {
+            nodes.insert_with(name.to_owned(), |id| {
+                StateNode::new(id, id.index() as u32, Vec2::ZERO, WHITE)
+            });
+        }
+        let (a, b) = (NodeId::from_index(0), NodeId::from_index(1));
+        let mut messages = Arena::new();
+        for (name, src, dest, tip) in [
+            ("0", a, b, "Ping"),
+            ("1", b, a, "Pong"),
+            ("2", a, a, "Ping"),
+        ] {
+            let sent = SimTime::from_secs(1.);
+            let delivered = SimTime::from_secs(1.5);
+            let msg = StateMessage::new(
+                &nodes[src],
+                dest,
+                tip.to_owned(),
+                String::new(),
+                sent,
+                vec![delivered],
+                Vec::new(),
+                delivered,
+            );
+            messages.insert(name.to_owned(), msg);
+        }
+
+        let mut stats = LatencyStats::default();
+        stats.update(&messages, &nodes, HiddenByFilters::default(), |_| true);
+        assert_eq!(stats.all.samples.len(), 2);
+        assert_eq!(stats.by_link.len(), 2);
+        stats.grouping = LatencyGrouping::Link;
+        stats.selected = Some(1);
+
+        // the same filters keep the samples and the selection
+        stats.update(&messages, &nodes, HiddenByFilters::default(), |_| false);
+        assert_eq!(stats.selected, Some(1));
+
+        let hidden = HiddenByFilters {
+            nodes: Vec::new(),
+            types: vec!["Pong".to_owned()],
+        };
+        stats.update(&messages, &nodes, hidden, |msg| msg.tip != "Pong");
+        assert_eq!(stats.selected, None);
+        let names: Vec<&str> = stats
+            .by_type
+            .iter()
+            .map(|group| group.name.as_str())
+            .collect();
+        assert_eq!(names, ["Ping"]);
+        assert_eq!(stats.by_link[0].link, Some((a, b)));
+        assert_eq!(stats.percentile(&stats.all.samples, 1.), 0.5);
+    }
+}
@@ -1,32 +1,20 @@
+use dslab_trace::{arena::NodeId, SimTime};
+
 #[derive(Debug, Clone)]
 pub struct StateLocalMessage {
-    pub id: String,
-    pub time: f64,
-    pub node: String,
+    pub time: SimTime,
+    pub node: NodeId,
+    pub tip: String,
     pub data: String,
-    pub msg_type: LocalMessageType,
 }
 
 impl StateLocalMessage {
-    pub fn new(
-        id: String,
-        time: f64,
-        node: String,
-        data: String,
-        msg_type: LocalMessageType,
-    ) -> Self {
+    pub fn new(time: SimTime, node: NodeId, tip: String, data: String) -> Self {
         Self {
-            id,
             time,
             node,
+            tip,
             data,
-            msg_type,
         }
     }
 }
-
-#[derive(Clone, Debug)]
-pub enum LocalMessageType {
-    Sent,
-    Received,
-}
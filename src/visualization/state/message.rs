@@ -1,187 +1,350 @@
-use std::{cell::RefCell, rc::Rc};
+use std::f32::consts::PI;
 
-use crate::visualization::utilities::*;
-use egui::Context;
+use crate::visualization::{batch::ShapeBatch, config::config, json_tree::show_json, utilities::*};
+use dslab_trace::{
+    arena::{Arena, NodeId},
+    trace::{CopyState, Corruption, MessageCopy, MessageState},
+    SimTime,
+};
+use egui::{Context, RichText, ScrollArea};
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::{node::*, state::State};
 
+/// One copy of a message on its way. A lost message has a single flight that never arrives.
+#[derive(Debug, Clone)]
+pub struct Flight {
+    pub pos: Vec2,
+    /// Time the copy reaches the destination. A lost message disappears at this time.
+    pub time_arrived: SimTime,
+    /// State of the copy on the last update, an arrived copy waits in the queue until handled.
+    pub state: CopyState,
+}
+
 #[derive(Debug, Clone)]
 pub struct StateMessage {
-    pub id: String,
-    pub relative_pos: Vec2,
-    pub src: Rc<RefCell<StateNode>>,
-    pub dest: Rc<RefCell<StateNode>>,
+    pub src: NodeId,
+    pub dest: NodeId,
     pub tip: String,
     pub data: String,
-    pub status: MessageStatus,
-    pub time_sent: f32,
-    pub time_delivered: f32,
-    pub copies_received: u64,
+    /// State on the last update, decided as in `Trace::state_at`.
+    pub status: MessageState,
+    pub time_sent: SimTime,
+    /// Delivery times of the copies of the message, in the order of the trace.
+    pub deliveries: Vec<SimTime>,
+    /// Copies that reach the destination, see `Message::copies`.
+    pub copies: Vec<MessageCopy>,
+    pub flights: Vec<Flight>,
+    pub corruption: Option<Corruption>,
+    /// Line diff of the corrupted payload, computed once when the trace is loaded.
+    /// `None` if the payloads are too long to diff.
+    pub payload_diff: Option<Vec<DiffLine>>,
     pub last_color_change: f64,
     pub color: Color,
 }
 
 impl StateMessage {
+    /// A message that never arrives disappears at `time_lost`, see `Message::time_lost`.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        id: String,
-        src: Rc<RefCell<StateNode>>,
-        dest: Rc<RefCell<StateNode>>,
+        src: &StateNode,
+        dest: NodeId,
         tip: String,
         data: String,
-        status: MessageStatus,
-        time_sent: f32,
-        time_delivered: f32,
-        copies_received: u64,
+        time_sent: SimTime,
+        deliveries: Vec<SimTime>,
+        copies: Vec<MessageCopy>,
+        time_lost: SimTime,
     ) -> Self {
-        let relative_pos = get_relative_pos(src.borrow().get_pos());
-        let color = src.borrow().color;
+        let arrivals = if copies.is_empty() {
+            vec![time_lost]
+        } else {
+            copies.iter().map(|copy| copy.arrival).collect()
+        };
+        let flights = arrivals
+            .into_iter()
+            .map(|time_arrived| Flight {
+                pos: src.get_pos(),
+                time_arrived,
+                state: CopyState::InFlight,
+            })
+            .collect();
         Self {
-            id,
-            relative_pos,
-            src,
+            src: src.id,
             dest,
             tip,
             data,
-            status,
+            status: MessageState::InFlight,
             time_sent,
-            time_delivered,
-            copies_received,
+            deliveries,
+            copies,
+            flights,
+            corruption: None,
+            payload_diff: None,
             last_color_change: 0.,
-            color,
+            color: src.color,
         }
     }
 
-    pub fn update_pos(&mut self, new_pos: Vec2) {
-        self.relative_pos = get_relative_pos(new_pos);
+    /// Returns the message to the state before it is sent, for replaying the trace.
+    pub fn reset(&mut self) {
+        self.status = MessageState::InFlight;
+        for flight in &mut self.flights {
+            flight.state = CopyState::InFlight;
+        }
+    }
+
+    /// Latency of the first delivered copy.
+    pub fn latency(&self) -> Option<f64> {
+        self.deliveries.first().map(|&time| time - self.time_sent)
+    }
+
+    pub fn is_corrupted_at(&self, time: SimTime) -> bool {
+        self.corruption
+            .as_ref()
+            .is_some_and(|corruption| time >= corruption.time)
     }
 
-    pub fn get_pos(&self) -> Vec2 {
-        get_absolute_pos(self.relative_pos)
+    pub fn flights_in_air(&self) -> impl Iterator<Item = (usize, &Flight)> {
+        self.flights
+            .iter()
+            .enumerate()
+            .filter(|(_, flight)| flight.state == CopyState::InFlight)
     }
 
-    pub fn get_direction(&self) -> Vec2 {
-        self.dest.borrow().get_pos() - self.get_pos()
+    /// Copies that reached the destination and wait for it to handle them.
+    pub fn flights_queued(&self) -> impl Iterator<Item = (usize, &Flight)> {
+        self.flights
+            .iter()
+            .enumerate()
+            .filter(|(_, flight)| flight.state == CopyState::Queued)
     }
 
-    pub fn get_own_speed(&self, current_time: f32) -> f32 {
-        let direction = self.get_direction();
-        let travel_time_left = self.time_delivered - current_time;
-        let mut own_speed = if !self.is_dropped() {
-            1.0 / ((get_fps() as f32) * travel_time_left / direction.length())
+    /// Part of the way from the source to the destination a flight covered at `current_time`.
+    /// Lost messages cover only `LOST_MESSAGE_PATH_SHARE` of the way.
+    pub fn progress(&self, flight: &Flight, current_time: SimTime, easing: Easing) -> f32 {
+        let duration = flight.time_arrived - self.time_sent;
+        let linear = if duration > 0. {
+            ((current_time - self.time_sent) / duration).clamp(0., 1.) as f32
         } else {
-            1.0 / ((get_fps() as f32) * 3.0 / direction.length())
+            1.
         };
-        if own_speed < 0. {
-            own_speed = MAX_MESSAGE_SPEED;
-        }
-        own_speed
+        let share = if self.is_dropped() {
+            LOST_MESSAGE_PATH_SHARE
+        } else {
+            1.
+        };
+        easing.apply(linear) * share
     }
 
-    pub fn update(&mut self, global_speed: f32, current_time: f32) {
-        let direction = self.get_direction();
-        let own_speed = self.get_own_speed(current_time);
-
-        self.update_pos(self.get_pos() + direction.normalize() * own_speed * global_speed);
+    pub fn update(
+        &mut self,
+        nodes: &Arena<NodeId, StateNode>,
+        current_time: SimTime,
+        easing: Easing,
+        color: Color,
+    ) {
+        let src_pos = nodes[self.src].get_pos();
+        let dest_pos = nodes[self.dest].get_pos();
+        for i in 0..self.flights.len() {
+            let progress = self.progress(&self.flights[i], current_time, easing);
+            self.flights[i].pos = src_pos.lerp(dest_pos, progress);
+        }
 
         let time = get_time();
         if self.is_dropped() && time - self.last_color_change >= 0.3 {
-            self.color = if self.color == BLACK {
-                self.src.borrow().color
-            } else {
-                BLACK
-            };
+            self.color = if self.color == BLACK { color } else { BLACK };
             self.last_color_change = time;
         }
         if !self.is_dropped() {
-            self.color = self.src.borrow().color
+            self.color = color;
         };
     }
 
-    pub fn update_with_jump(&mut self, global_speed: f32, current_time: f32, delta: f32) {
-        let direction = self.get_direction();
-        let own_speed = self.get_own_speed(current_time);
-        let jump_dist = own_speed * global_speed * delta;
-        self.update_pos(self.get_pos() + direction.normalize() * jump_dist);
+    pub fn draw(&self, state: &State, batch: &mut ShapeBatch) {
+        let radius = state.get_msg_radius();
+        let segments = state.circle_segments(radius);
+        let corrupted = self.is_corrupted_at(state.current_time);
+        for (_, flight) in self.flights_in_air() {
+            if state.is_visible(flight.pos, radius) {
+                batch.circle(flight.pos, radius, segments, self.color);
+                if corrupted {
+                    let color = config().theme.corrupted_message.0;
+                    batch.arc(flight.pos, radius * 1.5, 2., 0., 2. * PI, segments, color);
+                }
+            }
+        }
     }
 
-    pub fn draw(&self, state: &State) {
-        let pos = self.get_pos();
-        draw_circle(pos.x, pos.y, state.get_msg_radius(), self.color);
-        if self.is_duplicated() {
-            let font_size = (state.get_msg_radius() * 2.0).floor() as u16;
-            let text = self.copies_received.to_string();
-            let text_size = measure_text(&text, None, font_size, 1.0);
-            let text_position = Vec2::new(
-                pos.x - text_size.width / 2.0,
-                pos.y + text_size.height / 2.0,
-            );
-
-            draw_text_ex(
-                &text,
-                text_position.x,
-                text_position.y,
-                TextParams {
-                    font_size,
-                    color: BLACK,
-                    ..Default::default()
-                },
-            );
+    /// Numbers the copies of a duplicated message.
+    pub fn draw_label(&self, state: &State) {
+        if !self.is_duplicated() {
+            return;
+        }
+        let radius = state.get_msg_radius();
+        for (i, flight) in self.flights_in_air() {
+            if state.is_visible(flight.pos, radius) {
+                draw_centered_text(&(i + 1).to_string(), flight.pos, radius * 2.0, BLACK);
+            }
         }
     }
 
-    pub fn draw_ui(&self, egui_ctx: &Context, show_window: &mut bool) {
-        egui::Window::new(format!("Message {}", self.id))
+    pub fn draw_ui(&self, egui_ctx: &Context, show_window: &mut bool, id: &str, state: &State) {
+        egui::Window::new(format!("Message {}", id))
             .open(show_window)
             .show(egui_ctx, |ui| {
-                ui.label(format!("From: {}", self.src.borrow().id));
-                ui.label(format!("To: {}", self.dest.borrow().id));
-                if self.is_duplicated() {
-                    ui.label(format!("Duplicated {} times", self.copies_received));
+                ui.label(format!("From: {}", state.nodes[self.src].node_id));
+                ui.label(format!("To: {}", state.nodes[self.dest].node_id));
+                ui.label(format!("Sent at: {}", format_time(self.time_sent)));
+                if state.current_time >= self.time_sent {
+                    ui.label(format!("Status: {}", self.status_text()));
+                }
+                if let Some(latency) = self.latency() {
+                    ui.label(format!("Latency: {}", format_duration(latency)));
                 }
                 ui.label(format!("Type: {}", self.tip));
-                ui.label(format!("Data: {}", self.data.clone()));
+                ui.label("Data:");
+                ScrollArea::vertical()
+                    .id_source("data")
+                    .max_height(screen_height() * 0.4)
+                    .show(ui, |ui| show_json(ui, "data", &self.data));
+                if let Some(corruption) = &self.corruption {
+                    ui.separator();
+                    ui.strong(format!("Corrupted at {}", format_time(corruption.time)));
+                    ScrollArea::vertical()
+                        .id_source("payload diff")
+                        .max_height(screen_height() * 0.3)
+                        .show(ui, |ui| match &self.payload_diff {
+                            Some(diff) => {
+                                for line in diff {
+                                    let (text, color) = match line {
+                                        DiffLine::Same(line) => (format!("  {}", line), GRAY),
+                                        DiffLine::Removed(line) => (format!("- {}", line), RED),
+                                        DiffLine::Added(line) => (format!("+ {}", line), GREEN),
+                                    };
+                                    let text = RichText::new(text).monospace();
+                                    ui.label(text.color(egui_color(color)));
+                                }
+                            }
+                            None => {
+                                ui.columns(2, |columns| {
+                                    columns[0].strong("Sent");
+                                    columns[0].monospace(&corruption.original_data);
+                                    columns[1].strong("Delivered");
+                                    columns[1].monospace(&corruption.delivered_data);
+                                });
+                            }
+                        });
+                }
+                if !self.copies.is_empty() {
+                    ui.separator();
+                    ui.strong(format!("Copies ({})", self.copies.len()));
+                    for (i, copy) in self.copies.iter().enumerate() {
+                        let status = match copy.state_at(state.current_time) {
+                            CopyState::InFlight => "in flight",
+                            CopyState::Queued => "queued",
+                            CopyState::Delivered => "delivered",
+                        };
+                        let mut line = format!("#{}: {}", i + 1, status);
+                        if copy.delivery != Some(copy.arrival) {
+                            line += &format!(", arrives at {}", format_time(copy.arrival));
+                        }
+                        if let Some(time) = copy.delivery {
+                            line += &format!(
+                                ", delivered at {}, latency {}",
+                                format_time(time),
+                                format_duration(time - self.time_sent)
+                            );
+                        }
+                        ui.label(line);
+                    }
+                }
             });
     }
 
+    /// Describes the status with the reason of a drop.
+    pub fn status_text(&self) -> String {
+        match self.status {
+            MessageState::Dropped(reason) => format!("Dropped ({})", reason.description()),
+            MessageState::InFlight => "On the way".to_owned(),
+            status => format!("{:?}", status),
+        }
+    }
+
+    /// Whether the message never reaches the destination. A message without copies is
+    /// checked as `MessageState::of_copies` checks it once lost, so that it blinks on its way
+    /// and a message a node sends to itself does not.
     pub fn is_dropped(&self) -> bool {
-        self.copies_received == 0
+        let never_arrives = self.copies.is_empty()
+            && matches!(
+                MessageState::of_copies(&[], self.src == self.dest, true),
+                MessageState::Dropped(_)
+            );
+        never_arrives || matches!(self.status, MessageState::Dropped(_))
     }
 
     pub fn is_duplicated(&self) -> bool {
-        self.copies_received > 1
+        self.copies.len() > 1
     }
 
-    pub fn is_delivered(&self, current_time: f32) -> bool {
-        let pos = self.get_pos();
-        if !self.is_dropped() {
-            calc_dist(pos, self.dest.borrow().get_pos()) < 5.0
-                || current_time >= self.time_delivered
-        } else {
-            let overall_dist = calc_dist(self.src.borrow().get_pos(), self.dest.borrow().get_pos());
-            calc_dist(self.src.borrow().get_pos(), pos) >= overall_dist * 0.25
+    /// Updates the states of the copies and of the message and returns the delivery times
+    /// of the copies handled since the last call. A lost message disappears at the arrival
+    /// time of its only flight.
+    pub fn update_status(&mut self, current_time: SimTime) -> Vec<SimTime> {
+        let mut delivered = Vec::new();
+        for (flight, copy) in self.flights.iter_mut().zip(&self.copies) {
+            let state = copy.state_at(current_time);
+            if state == CopyState::Delivered && flight.state != CopyState::Delivered {
+                delivered.extend(copy.delivery);
+            }
+            flight.state = state;
         }
+        let copies: Vec<CopyState> = self.flights[..self.copies.len()]
+            .iter()
+            .map(|flight| flight.state)
+            .collect();
+        let lost = current_time >= self.flights[0].time_arrived;
+        self.status = MessageState::of_copies(&copies, self.src == self.dest, lost);
+        delivered
     }
+}
+
+/// How a message speeds up and slows down on its way. The time it is sent
+/// and the time it arrives do not depend on easing.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
 
-    pub fn update_status(&mut self, current_time: f32) {
-        if self.is_delivered(current_time) {
-            self.status = if self.is_dropped() && self.src.borrow().id != self.dest.borrow().id {
-                MessageStatus::Dropped
-            } else {
-                MessageStatus::Delivered
-            };
+impl Easing {
+    pub const ALL: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease in",
+            Easing::EaseOut => "Ease out",
+            Easing::EaseInOut => "Ease in-out",
         }
     }
 
-    pub fn set_status(&mut self, status: MessageStatus) {
-        self.status = status;
+    /// Maps the share of the travel time passed to the share of the way covered.
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1. - (1. - t) * (1. - t),
+            Easing::EaseInOut => t * t * (3. - 2. * t),
+        }
     }
 }
-
-#[derive(Clone, Debug, PartialEq)]
-pub enum MessageStatus {
-    Queued,
-    OnTheWay,
-    Dropped,
-    Delivered,
-}
@@ -1,5 +1,10 @@
+pub mod coloring;
+pub mod event_log;
+pub mod latency;
 pub mod local_message;
 pub mod message;
 pub mod node;
+pub mod plots;
+#[allow(clippy::module_inception)]
 pub mod state;
 pub mod timer;
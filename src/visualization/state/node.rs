@@ -1,140 +1,325 @@
-use std::{
-    cell::RefCell,
-    collections::{HashMap, VecDeque},
-    rc::Rc,
-};
+use std::{collections::VecDeque, f32::consts::PI};
 
 use egui::{Context, ScrollArea};
 use macroquad::prelude::*;
+use serde_json::Value;
 
-use crate::visualization::utilities::*;
+use crate::visualization::{
+    batch::ShapeBatch,
+    config::config,
+    json_tree::{parse_json, show_json},
+    utilities::*,
+};
+use dslab_trace::{
+    arena::{Arena, LocalMessageId, MessageId, NodeId, TimerId},
+    SimTime,
+};
 
-use super::{local_message::StateLocalMessage, message::StateMessage, state::State, timer::*};
+use super::{
+    state::{State, UiAction},
+    timer::*,
+};
 
 #[derive(Debug, Clone)]
 pub struct StateNode {
-    pub name: String,
-    pub id: u32,
-    pub relative_pos: Vec2,
+    pub id: NodeId,
+    /// Id of the node in the trace.
+    pub node_id: u32,
+    pub pos: Vec2,
+    pub target_pos: Option<Vec2>,
     pub connected: bool,
     pub state: String,
-    pub local_messages_sent: Vec<StateLocalMessage>,
-    pub local_messages_received: Vec<StateLocalMessage>,
-    pub messages_sent: Vec<String>,
-    pub messages_received: Vec<String>,
-    pub timers: VecDeque<StateTimer>,
+    /// Values of the configured state badges, see `Config::state_badges`.
+    pub badges: Vec<Option<String>>,
+    pub local_messages_sent: Vec<LocalMessageId>,
+    pub local_messages_received: Vec<LocalMessageId>,
+    pub messages_sent: Vec<MessageId>,
+    /// Delivered copies of messages with their delivery times.
+    pub messages_received: Vec<(MessageId, SimTime)>,
+    pub timers: VecDeque<TimerId>,
+    /// Copies of messages that reached the node and wait to be handled, oldest first.
+    pub queue: Vec<(MessageId, usize)>,
     pub free_timer_slots: VecDeque<usize>,
     pub color: Color,
     pub show: bool,
+    pub pinned: bool,
 }
 
 impl StateNode {
-    pub fn new(name: String, id: u32, pos: Vec2, color: Color) -> Self {
-        let relative_pos = get_relative_pos(pos);
+    pub fn new(id: NodeId, node_id: u32, pos: Vec2, color: Color) -> Self {
         Self {
-            name,
             id,
-            relative_pos,
+            node_id,
+            pos,
+            target_pos: None,
             color,
             connected: true,
             state: String::from(""),
+            badges: Vec::new(),
             local_messages_sent: Vec::new(),
             local_messages_received: Vec::new(),
             messages_sent: Vec::new(),
             messages_received: Vec::new(),
             timers: VecDeque::new(),
-            free_timer_slots: (0..TIMERS_MAX_NUMBER).collect(),
+            queue: Vec::new(),
+            free_timer_slots: (0..config().timers_max_number).collect(),
             show: false,
+            pinned: false,
+        }
+    }
+
+    /// Returns the node to the state before it is started, for replaying the trace.
+    /// The position stays.
+    pub fn reset(&mut self) {
+        self.connected = true;
+        self.state.clear();
+        self.badges.clear();
+        self.local_messages_sent.clear();
+        self.local_messages_received.clear();
+        self.messages_sent.clear();
+        self.messages_received.clear();
+        self.timers.clear();
+        self.queue.clear();
+        self.free_timer_slots = (0..config().timers_max_number).collect();
+        self.show = false;
+    }
+
+    /// Reads the values of the state badges from the current state.
+    pub fn update_badges(&mut self) {
+        let badges = &config().state_badges;
+        if badges.is_empty() {
+            return;
         }
+        let state = parse_json(&self.state);
+        self.badges = badges
+            .iter()
+            .map(|badge| {
+                let value = state.as_ref()?.pointer(&badge.pointer)?;
+                Some(match value {
+                    Value::String(text) => text.clone(),
+                    value => value.to_string(),
+                })
+            })
+            .collect();
     }
 
     pub fn update_pos(&mut self, new_pos: Vec2) {
-        self.relative_pos = get_relative_pos(new_pos);
+        self.pos = new_pos;
+        self.target_pos = None;
+    }
+
+    /// Starts a smooth movement to the new position, see `animate`.
+    pub fn move_to(&mut self, new_pos: Vec2) {
+        self.target_pos = Some(new_pos);
+    }
+
+    pub fn animate(&mut self, dt: f32) {
+        if let Some(target) = self.target_pos {
+            let delta = target - self.pos;
+            if delta.length() < 0.5 {
+                self.pos = target;
+                self.target_pos = None;
+            } else {
+                self.pos += delta * f32::min(1., dt * LAYOUT_ANIMATION_SPEED);
+            }
+        }
     }
 
     pub fn get_pos(&self) -> Vec2 {
-        get_absolute_pos(self.relative_pos)
+        self.pos
     }
 
-    pub fn update(&mut self, current_time: f64) {
-        for timer in &mut self.timers {
-            if timer.k == -1 {
-                if !self.free_timer_slots.is_empty() {
-                    timer.k = *self.free_timer_slots.front().unwrap() as i32;
-                    self.free_timer_slots.pop_front();
-                }
-            } else if current_time >= timer.time_removed {
-                self.free_timer_slots.push_back(timer.k as usize);
+    pub fn update(&mut self, current_time: SimTime, timers: &mut Arena<TimerId, StateTimer>) {
+        for &timer in &self.timers {
+            let timer = &mut timers[timer];
+            if timer.k == -1 && !self.free_timer_slots.is_empty() {
+                timer.k = self.free_timer_slots.pop_front().unwrap() as i32;
             }
         }
-        self.timers
-            .retain(|timer| current_time < timer.time_removed);
+        self.timers.retain(|&timer| {
+            let timer = &timers[timer];
+            if timer.timer.is_active_at(current_time) {
+                return true;
+            }
+            if timer.k != -1 {
+                self.free_timer_slots.push_back(timer.k as usize);
+            }
+            false
+        });
     }
 
     pub fn check_for_hovered_timer(
         &self,
+        mouse_pos: Vec2,
         node_radius: f32,
         timer_radius: f32,
-    ) -> Option<StateTimer> {
-        let mut hovered_timer: Option<StateTimer> = None;
-        for timer in &self.timers {
-            if timer.check_hovered(self.get_pos(), node_radius, timer_radius) {
-                hovered_timer = Some(timer.clone());
+        timers: &Arena<TimerId, StateTimer>,
+    ) -> Option<TimerId> {
+        let mut hovered_timer = None;
+        for &timer in &self.timers {
+            if timers[timer].check_hovered(mouse_pos, self.get_pos(), node_radius, timer_radius) {
+                hovered_timer = Some(timer);
             }
         }
         hovered_timer
     }
 
-    pub fn draw(&self, state: &State) {
+    pub fn draw(&self, state: &State, batch: &mut ShapeBatch) {
         let pos = self.get_pos();
-        draw_circle(
-            pos.x,
-            pos.y,
-            state.get_node_radius(),
+        let radius = state.get_node_radius();
+        batch.circle(
+            pos,
+            radius,
+            state.circle_segments(radius),
             if self.connected {
                 self.color
             } else {
-                DEAD_NODE_COLOR
+                config().theme.dead_node.0
             },
         );
 
-        let font_size = (state.get_node_radius() * 2.0).floor() as u16;
-        let text_size = measure_text(&self.name, None, font_size, 1.0);
-        let text_position = Vec2::new(
-            pos.x - text_size.width / 2.0,
-            pos.y + text_size.height / 2.0,
-        );
-
-        let show_events = *state.ui_data.show_events_for_node.get(&self.name).unwrap();
+        self.draw_drop_badges(pos, state, batch);
+        if state.is_highlighted(self.id) {
+            let color = config().theme.highlighted_node.0;
+            batch.arc(
+                pos,
+                radius * 1.25,
+                3.,
+                0.,
+                2. * PI,
+                state.circle_segments(radius),
+                color,
+            );
+        }
 
-        if show_events && state.ui_data.show_timers {
-            for i in 0..self.timers.len() {
-                if self.timers[i].k == -1 {
+        if state.ui_data.show_events_for_node[&self.id] && state.ui_data.show_timers {
+            for &timer in &self.timers {
+                let timer = &state.timers[timer];
+                if timer.k == -1 {
                     break;
                 }
-                self.timers[i].draw(pos, state);
+                timer.draw(pos, state, batch);
             }
         }
+    }
 
-        draw_text_ex(
-            &self.name,
-            text_position.x,
-            text_position.y,
-            TextParams {
-                font_size,
-                color: WHITE,
-                ..Default::default()
-            },
+    /// Draws the node name. Called after all shapes are drawn, so that names stay on top.
+    pub fn draw_label(&self, state: &State) {
+        draw_centered_text(
+            state.nodes.name(self.id),
+            self.get_pos(),
+            state.get_node_radius() * 2.0,
+            config().theme.text.0,
+        );
+    }
+
+    /// Position of the `slot`-th message of the queue stack. The stack grows upwards
+    /// to the left of the node, `reach` away from its center.
+    pub fn queue_slot_pos(&self, slot: usize, reach: f32, msg_radius: f32) -> Vec2 {
+        let pos = self.get_pos();
+        Vec2::new(
+            pos.x - reach - msg_radius,
+            pos.y - slot as f32 * msg_radius * 2.2,
+        )
+    }
+
+    /// Draws the first queued messages as a stack, with an outline for the ones not drawn.
+    pub fn draw_queue(&self, state: &State, batch: &mut ShapeBatch) {
+        let radius = state.get_msg_radius();
+        let segments = state.circle_segments(radius);
+        for &(msg, i) in self.queue.iter().take(MAX_SHOWN_QUEUED_MESSAGES) {
+            let msg = &state.messages[msg];
+            batch.circle(msg.flights[i].pos, radius, segments, msg.color);
+        }
+        if self.queue.len() > MAX_SHOWN_QUEUED_MESSAGES {
+            let (msg, i) = self.queue[MAX_SHOWN_QUEUED_MESSAGES - 1];
+            let pos = state.messages[msg].flights[i].pos;
+            let color = config().theme.text.0;
+            batch.arc(pos, radius * 1.3, 1.5, 0., 2. * PI, segments, color);
+        }
+    }
+
+    /// Writes the number of queued messages above the stack.
+    pub fn draw_queue_label(&self, state: &State) {
+        let Some(&(msg, i)) = self
+            .queue
+            .get(MAX_SHOWN_QUEUED_MESSAGES - 1)
+            .or(self.queue.last())
+        else {
+            return;
+        };
+        let radius = state.get_msg_radius();
+        let pos = state.messages[msg].flights[i].pos - Vec2::new(0., radius * 2.5);
+        draw_centered_text(
+            &self.queue.len().to_string(),
+            pos,
+            radius * 2.,
+            config().theme.text.0,
         );
     }
 
+    /// Writes the values of the state badges under the node, below the timers,
+    /// in the colors configured for them.
+    pub fn draw_badges(&self, state: &State) {
+        let radius = state.get_node_radius();
+        let font_size = radius * 0.9;
+        let top = radius + 2. * state.get_timer_radius() + 5.;
+        let shown = config().state_badges.iter().zip(&self.badges);
+        for (k, (badge, value)) in shown.enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            let color = badge
+                .colors
+                .get(value)
+                .map_or(config().theme.text.0, |color| color.0);
+            let pos = self.get_pos() + Vec2::new(0., top + (k as f32 + 0.5) * font_size);
+            draw_centered_text(value, pos, font_size, color);
+        }
+    }
+
+    /// Marks a node that drops incoming messages with an arrow pointing into it
+    /// and a node that drops outgoing messages with an arrow pointing out of it.
+    pub fn draw_drop_badges(&self, pos: Vec2, state: &State, batch: &mut ShapeBatch) {
+        let radius = state.get_node_radius();
+        let size = radius * 0.6;
+        let color = config().theme.drop_badge.0;
+        if state.replay.network.drop_incoming.contains(&self.id) {
+            let direction = Vec2::new(1., 1.);
+            let tip = pos - direction.normalize() * radius;
+            batch.arrow_head(tip, direction, size, color);
+        }
+        if state.replay.network.drop_outgoing.contains(&self.id) {
+            let direction = Vec2::new(1., -1.);
+            let tip = pos + direction.normalize() * (radius + size);
+            batch.arrow_head(tip, direction, size, color);
+        }
+    }
+
+    /// Shows the node window and returns the actions of the links clicked in it.
     pub fn draw_ui(
         &self,
         egui_ctx: &Context,
         show_window: &mut bool,
-        state_messages: &HashMap<String, Rc<RefCell<StateMessage>>>,
-    ) {
-        egui::Window::new(format!("Node {}", self.name))
+        state: &State,
+    ) -> Vec<UiAction> {
+        let mut actions = Vec::new();
+        // the type filter lists message types only, local messages are always shown
+        let msg_type_filter = &state.ui_data.msg_type_filter;
+        let local_messages_sent: Vec<_> = self.local_messages_sent.iter().collect();
+        let local_messages_received: Vec<_> = self.local_messages_received.iter().collect();
+        let messages_sent: Vec<_> = self
+            .messages_sent
+            .iter()
+            .filter(|&&msg| msg_type_filter.is_shown(&state.messages[msg].tip))
+            .collect();
+        let messages_received: Vec<_> = self
+            .messages_received
+            .iter()
+            .filter(|(msg, _)| msg_type_filter.is_shown(&state.messages[*msg].tip))
+            .collect();
+
+        egui::Window::new(format!("Node {}", state.nodes.name(self.id)))
             .open(show_window)
             .show(egui_ctx, |ui| {
                 ui.label(format!(
@@ -145,59 +330,107 @@ impl StateNode {
                         "Disconnected"
                     }
                 ));
+                ui.label(format!(
+                    "Pinned: {}",
+                    if self.pinned { "yes" } else { "no" }
+                ));
                 ui.collapsing("State", |ui| {
                     ui.set_max_height(screen_height() * 0.3);
                     ScrollArea::vertical().show(ui, |ui| {
-                        ui.label(format!("{}", self.state));
+                        show_json(ui, "state", &self.state);
+                    });
+                    ui.set_max_height(f32::INFINITY);
+                });
+                let title = format!("Sent local messages ({})", local_messages_sent.len());
+                ui.collapsing(title, |ui| {
+                    ui.set_max_height(screen_height() * 0.3);
+                    ScrollArea::vertical().show(ui, |ui| {
+                        for &&id in &local_messages_sent {
+                            let msg = &state.local_messages[id];
+                            let text = format!("Sent at: {}", format_time(msg.time));
+                            UiAction::Seek(msg.time).link(ui, text, &mut actions);
+                            ui.label(format!("Type: {}", msg.tip));
+                            ui.label("Data:");
+                            show_json(ui, ("local message", id), &msg.data);
+                            ui.separator();
+                        }
                     });
                     ui.set_max_height(f32::INFINITY);
                 });
-                ui.collapsing("Sent local messages", |ui| {
+                let title = format!(
+                    "Received local messages ({})",
+                    local_messages_received.len()
+                );
+                ui.collapsing(title, |ui| {
                     ui.set_max_height(screen_height() * 0.3);
                     ScrollArea::vertical().show(ui, |ui| {
-                        for msg in &self.local_messages_sent {
-                            ui.label(format!("Sent at: {:.7}", msg.time));
-                            ui.label(format!("Data: {}", msg.data));
+                        for &&id in &local_messages_received {
+                            let msg = &state.local_messages[id];
+                            let text = format!("Received at: {}", format_time(msg.time));
+                            UiAction::Seek(msg.time).link(ui, text, &mut actions);
+                            ui.label(format!("Type: {}", msg.tip));
+                            ui.label("Data:");
+                            show_json(ui, ("local message", id), &msg.data);
                             ui.separator();
                         }
                     });
                     ui.set_max_height(f32::INFINITY);
                 });
-                ui.collapsing("Received local messages", |ui| {
+                let title = format!("Sent messages ({})", messages_sent.len());
+                ui.collapsing(title, |ui| {
                     ui.set_max_height(screen_height() * 0.3);
                     ScrollArea::vertical().show(ui, |ui| {
-                        for msg in &self.local_messages_received {
-                            ui.label(format!("Received at: {:.7}", msg.time));
-                            ui.label(format!("Data: {}", msg.data));
+                        for &&id in &messages_sent {
+                            let msg = &state.messages[id];
+                            let text = format!("Message {}", state.messages.name(id));
+                            UiAction::OpenMessage(id).link(ui, text, &mut actions);
+                            let text = format!("To: {}", state.nodes.name(msg.dest));
+                            UiAction::OpenNode(msg.dest).link(ui, text, &mut actions);
+                            let text = format!("Sent at: {}", format_time(msg.time_sent));
+                            UiAction::Seek(msg.time_sent).link(ui, text, &mut actions);
+                            ui.label(format!("Status: {}", msg.status_text()));
+                            ui.label(format!("Type: {}", msg.tip));
+                            ui.label("Data:");
+                            show_json(ui, ("sent", id), &msg.data);
                             ui.separator();
                         }
                     });
                     ui.set_max_height(f32::INFINITY);
                 });
-                ui.collapsing("Sent messages", |ui| {
+                let title = format!("Received messages ({})", messages_received.len());
+                ui.collapsing(title, |ui| {
                     ui.set_max_height(screen_height() * 0.3);
                     ScrollArea::vertical().show(ui, |ui| {
-                        for msg_id in &self.messages_sent {
-                            let msg = state_messages.get(msg_id).unwrap().borrow();
-                            ui.label(format!("To: {}", msg.dest.borrow().name));
-                            ui.label(format!("Sent at: {:.7}", msg.time_sent));
-                            ui.label(format!("Status: {:?}", msg.status));
+                        for (i, &&(id, time)) in messages_received.iter().enumerate() {
+                            let msg = &state.messages[id];
+                            let text = format!("Message {}", state.messages.name(id));
+                            UiAction::OpenMessage(id).link(ui, text, &mut actions);
+                            let text = format!("From: {}", state.nodes.name(msg.src));
+                            UiAction::OpenNode(msg.src).link(ui, text, &mut actions);
+                            let text = format!("Received at: {}", format_time(time));
+                            UiAction::Seek(time).link(ui, text, &mut actions);
                             ui.label(format!("Type: {}", msg.tip));
-                            ui.label(format!("Data: {}", msg.data));
+                            ui.label("Data:");
+                            show_json(ui, ("received", i), &msg.data);
                             ui.separator();
                         }
                     });
                     ui.set_max_height(f32::INFINITY);
                 });
-                ui.collapsing("Received messages", |ui| {
+                let title = format!("Queued messages ({})", self.queue.len());
+                ui.collapsing(title, |ui| {
                     ui.set_max_height(screen_height() * 0.3);
                     ScrollArea::vertical().show(ui, |ui| {
-                        for msg_id in &self.messages_received {
-                            let msg = state_messages.get(msg_id).unwrap().borrow();
-                            ui.label(format!("From: {}", msg.src.borrow().name));
-                            ui.label(format!("Received at: {:.7}", msg.time_delivered));
+                        for &(id, i) in &self.queue {
+                            let msg = &state.messages[id];
+                            let text = format!("Message {}", state.messages.name(id));
+                            UiAction::OpenMessage(id).link(ui, text, &mut actions);
+                            let text = format!("From: {}", state.nodes.name(msg.src));
+                            UiAction::OpenNode(msg.src).link(ui, text, &mut actions);
+                            let arrived = msg.flights[i].time_arrived;
+                            let text = format!("Arrived at: {}", format_time(arrived));
+                            UiAction::Seek(arrived).link(ui, text, &mut actions);
                             ui.label(format!("Type: {}", msg.tip));
-                            ui.label(format!("Data: {}", msg.data));
                             ui.separator();
                         }
                     });
@@ -206,16 +439,22 @@ impl StateNode {
                 ui.collapsing("Current timers", |ui| {
                     ui.set_max_height(screen_height() * 0.3);
                     ScrollArea::vertical().show(ui, |ui| {
-                        for timer in &self.timers {
+                        for &timer in &self.timers {
+                            let timer = &state.timers[timer].timer;
                             ui.label(format!("Timer {}", timer.name));
-                            ui.label(format!("Time set: {:.7}", timer.time_set));
-                            ui.label(format!("Delay: {}", timer.delay));
-                            ui.label(format!("Time removed: {:.7}", timer.time_removed));
+                            let text = format!("Time set: {}", format_time(timer.time_set));
+                            UiAction::Seek(timer.time_set).link(ui, text, &mut actions);
+                            ui.label(format!("Delay: {}", format_duration(timer.delay)));
+                            if let Some(removed) = timer.time_removed {
+                                let text = format!("Time removed: {}", format_time(removed));
+                                UiAction::Seek(removed).link(ui, text, &mut actions);
+                            }
                             ui.separator();
                         }
                     });
                     ui.set_max_height(f32::INFINITY);
                 });
             });
+        actions
     }
 }
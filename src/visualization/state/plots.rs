@@ -0,0 +1,374 @@
+use egui::{
+    plot::{Legend, Line, Plot, VLine},
+    ComboBox, Context, TextEdit,
+};
+use macroquad::prelude::screen_height;
+use serde_json::Value;
+
+use crate::visualization::{config::config, json_tree::parse_json, utilities::*};
+use dslab_trace::{
+    arena::{Arena, MessageId, NodeId},
+    trace::{Event, EventKind},
+    Replay, SimTime, Trace,
+};
+
+use super::{node::StateNode, state::HiddenByFilters};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlotMetric {
+    StateField,
+    InFlight,
+    Drops,
+}
+
+impl PlotMetric {
+    pub const ALL: [PlotMetric; 3] = [
+        PlotMetric::StateField,
+        PlotMetric::InFlight,
+        PlotMetric::Drops,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlotMetric::StateField => "State field",
+            PlotMetric::InFlight => "Messages in flight",
+            PlotMetric::Drops => "Dropped messages",
+        }
+    }
+}
+
+/// Time a message was sent and the time it stopped being in flight.
+struct Flight {
+    msg: MessageId,
+    sent: SimTime,
+    /// Not set if the message was dropped on sending.
+    landed: Option<SimTime>,
+    dropped: bool,
+}
+
+/// Series over the whole trace shown in the plots panel. Times are in the configured unit.
+pub struct Plots {
+    pub metric: PlotMetric,
+    /// JSON pointer to the plotted field of the process state.
+    pub pointer: String,
+    flights: Vec<Flight>,
+    /// Messages in flight and dropped ones among the messages shown by `counted_for`.
+    in_flight: Vec<[f64; 2]>,
+    drops: Vec<[f64; 2]>,
+    counted_for: Option<HiddenByFilters>,
+    end: f64,
+    /// Values of the field at `field_pointer` for each node whose state has it.
+    field: Vec<(NodeId, Vec<[f64; 2]>)>,
+    field_pointer: Option<String>,
+}
+
+impl Default for Plots {
+    fn default() -> Self {
+        Self {
+            metric: PlotMetric::InFlight,
+            pointer: String::new(),
+            flights: Vec::new(),
+            in_flight: Vec::new(),
+            drops: Vec::new(),
+            counted_for: None,
+            end: 0.,
+            field: Vec::new(),
+            field_pointer: None,
+        }
+    }
+}
+
+impl Plots {
+    /// Finds when each message was in flight and whether it was dropped. Drops on sending
+    /// are taken from a replay of the whole trace, as the viewer does.
+    pub fn load(&mut self, trace: &Trace) {
+        let mut replay = Replay::new(trace);
+        let mut end = trace
+            .events
+            .last()
+            .map_or(SimTime::ZERO, |event| event.time);
+        replay.advance(trace, end);
+        let mean_latency = trace.mean_latency();
+        self.flights = replay
+            .sent
+            .iter()
+            .map(|&id| {
+                let msg = &trace.messages[id];
+                let sent = msg.time_sent;
+                if replay.drop_reason(id).is_some() {
+                    return Flight {
+                        msg: id,
+                        sent,
+                        landed: None,
+                        dropped: true,
+                    };
+                }
+                let arrival = msg.copies().iter().map(|copy| copy.arrival).max();
+                let landed = arrival.unwrap_or_else(|| msg.time_lost(mean_latency));
+                end = end.max(landed);
+                Flight {
+                    msg: id,
+                    sent,
+                    landed: Some(landed),
+                    dropped: arrival.is_none() && msg.src != msg.dest,
+                }
+            })
+            .collect();
+        self.end = to_plot_time(end);
+        self.counted_for = None;
+        self.field_pointer = None;
+    }
+
+    /// Counts the shown messages in flight and dropped ones over time, if the filters changed.
+    pub fn update_counts(&mut self, hidden: HiddenByFilters, show: impl Fn(MessageId) -> bool) {
+        if self.counted_for.as_ref() == Some(&hidden) {
+            return;
+        }
+        let mut in_flight = Vec::new();
+        let mut drops = Vec::new();
+        for flight in self.flights.iter().filter(|flight| show(flight.msg)) {
+            if let Some(landed) = flight.landed {
+                in_flight.push((flight.sent, 1.));
+                in_flight.push((landed, -1.));
+            }
+            if flight.dropped {
+                drops.push((flight.landed.unwrap_or(flight.sent), 1.));
+            }
+        }
+        self.in_flight = accumulate(in_flight, self.end);
+        self.drops = accumulate(drops, self.end);
+        self.counted_for = Some(hidden);
+    }
+
+    /// Reads the plotted field from every state update, if the pointer changed.
+    /// Numbers, booleans and strings with numbers are plotted, other values are skipped.
+    fn update_field(&mut self, events: &[Event]) {
+        if self.field_pointer.as_ref() == Some(&self.pointer) {
+            return;
+        }
+        let mut field: Vec<(NodeId, Vec<(SimTime, f64)>)> = Vec::new();
+        for event in events {
+            let EventKind::NodeStateUpdated(node, state) = &event.kind else {
+                continue;
+            };
+            let value = parse_json(state)
+                .as_ref()
+                .and_then(|state| state.pointer(&self.pointer))
+                .and_then(plot_value);
+            let Some(value) = value else {
+                continue;
+            };
+            match field.iter_mut().find(|(id, _)| id == node) {
+                Some((_, changes)) => changes.push((event.time, value)),
+                None => field.push((*node, vec![(event.time, value)])),
+            }
+        }
+        field.sort_by_key(|(node, _)| *node);
+        self.field = field
+            .into_iter()
+            .map(|(node, changes)| (node, steps(changes, self.end)))
+            .collect();
+        self.field_pointer = Some(self.pointer.clone());
+    }
+
+    /// Shows the panel and returns the time clicked on the plot.
+    pub fn draw_ui(
+        &mut self,
+        egui_ctx: &Context,
+        events: &[Event],
+        nodes: &Arena<NodeId, StateNode>,
+        current_time: SimTime,
+    ) -> Option<SimTime> {
+        egui::TopBottomPanel::bottom("plots")
+            .resizable(true)
+            .default_height(screen_height() * 0.25)
+            .show(egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ComboBox::from_label("Metric")
+                        .selected_text(self.metric.name())
+                        .show_ui(ui, |ui| {
+                            for metric in PlotMetric::ALL {
+                                ui.selectable_value(&mut self.metric, metric, metric.name());
+                            }
+                        });
+                    if self.metric == PlotMetric::StateField {
+                        ui.add(TextEdit::singleline(&mut self.pointer).hint_text("/term"));
+                    }
+                });
+                let lines: Vec<Line> = match self.metric {
+                    PlotMetric::StateField => {
+                        self.update_field(events);
+                        self.field
+                            .iter()
+                            .map(|(node, points)| {
+                                Line::new(points.clone())
+                                    .name(nodes.name(*node))
+                                    .color(egui_color(nodes[*node].color))
+                            })
+                            .collect()
+                    }
+                    PlotMetric::InFlight => vec![Line::new(self.in_flight.clone())],
+                    PlotMetric::Drops => vec![Line::new(self.drops.clone())],
+                };
+                let response = Plot::new("plot")
+                    .legend(Legend::default())
+                    .include_y(0.)
+                    .allow_boxed_zoom(false)
+                    .show(ui, |plot_ui| {
+                        for line in lines {
+                            plot_ui.line(line);
+                        }
+                        let now = to_plot_time(current_time);
+                        plot_ui.vline(VLine::new(now).color(egui_color(config().theme.text.0)));
+                        if plot_ui.plot_clicked() {
+                            plot_ui.pointer_coordinate().map(|point| point.x)
+                        } else {
+                            None
+                        }
+                    });
+                response
+                    .inner
+                    .map(|x| SimTime::from_secs(x.max(0.) / config().time_unit.per_second()))
+            })
+            .inner
+    }
+}
+
+fn to_plot_time(time: SimTime) -> f64 {
+    time.as_secs() * config().time_unit.per_second()
+}
+
+fn plot_value(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::Bool(flag) => Some(if *flag { 1. } else { 0. }),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Sums changes of a counter into a step line starting at zero.
+fn accumulate(mut changes: Vec<(SimTime, f64)>, end: f64) -> Vec<[f64; 2]> {
+    changes.sort_by_key(|(time, _)| *time);
+    let mut total = 0.;
+    let mut values = vec![(SimTime::ZERO, 0.)];
+    values.extend(changes.into_iter().map(|(time, delta)| {
+        total += delta;
+        (time, total)
+    }));
+    steps(values, end)
+}
+
+/// Turns values set at some times into a step line that ends at `end`.
+fn steps(values: Vec<(SimTime, f64)>, end: f64) -> Vec<[f64; 2]> {
+    let mut points: Vec<[f64; 2]> = Vec::new();
+    for (time, value) in values {
+        let x = to_plot_time(time);
+        if let Some(&[_, last]) = points.last() {
+            points.push([x, last]);
+        }
+        points.push([x, value]);
+    }
+    if let Some(&[x, last]) = points.last() {
+        points.push([end.max(x), last]);
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dslab_trace::arena::ArenaId;
+
+    fn secs(secs: f64) -> SimTime {
+        SimTime::from_secs(secs)
+    }
+
+    #[test]
+    fn steps_hold_values_until_the_next_change() {
+        let values = vec![(secs(1.), 5.), (secs(3.), 2.)];
+        assert_eq!(
+            steps(values, 4.),
+            vec![[1., 5.], [3., 5.], [3., 2.], [4., 2.]]
+        );
+    }
+
+    #[test]
+    fn steps_end_at_the_last_value_if_it_is_after_the_end() {
+        assert_eq!(steps(vec![(secs(2.), 1.)], 1.), vec![[2., 1.], [2., 1.]]);
+        assert!(steps(Vec::new(), 1.).is_empty());
+    }
+
+    #[test]
+    fn accumulate_sums_changes_in_time_order() {
+        let changes = vec![(secs(2.), -1.), (secs(1.), 1.), (secs(1.5), 1.)];
+        assert_eq!(
+            accumulate(changes, 3.),
+            vec![
+                [0., 0.],
+                [1., 0.],
+                [1., 1.],
+                [1.5, 1.],
+                [1.5, 2.],
+                [2., 2.],
+                [2., 1.],
+                [3., 1.],
+            ]
+        );
+    }
+
+    #[test]
+    fn accumulate_without_changes_stays_at_zero() {
+        assert_eq!(accumulate(Vec::new(), 2.), vec![[0., 0.], [2., 0.]]);
+    }
+
+    #[test]
+    fn counts_follow_the_filters() {
+        let msg = MessageId::from_index;
+        let mut plots = Plots {
+            flights: vec![
+                Flight {
+                    msg: msg(0),
+                    sent: secs(1.),
+                    landed: Some(secs(2.)),
+                    dropped: false,
+                },
+                Flight {
+                    msg: msg(1),
+                    sent: secs(1.),
+                    landed: None,
+                    dropped: true,
+                },
+                Flight {
+                    msg: msg(2),
+                    sent: secs(1.5),
+                    landed: Some(secs(3.)),
+                    dropped: true,
+                },
+            ],
+            end: 3.,
+            ..Plots::default()
+        };
+        plots.update_counts(HiddenByFilters::default(), |_| true);
+        assert_eq!(plots.in_flight.last(), Some(&[3., 0.]));
+        assert_eq!(
+            plots.drops,
+            vec![[0., 0.], [1., 0.], [1., 1.], [3., 1.], [3., 2.], [3., 2.]]
+        );
+
+        // the counts are kept while the filters are the same
+        plots.update_counts(HiddenByFilters::default(), |_| false);
+        assert_eq!(plots.drops.last(), Some(&[3., 2.]));
+
+        let hidden = HiddenByFilters {
+            nodes: Vec::new(),
+            types: vec!["Lost".to_owned()],
+        };
+        plots.update_counts(hidden, |id| id == msg(0));
+        assert_eq!(
+            plots.in_flight,
+            vec![[0., 0.], [1., 0.], [1., 1.], [2., 1.], [2., 0.], [3., 0.]]
+        );
+        assert_eq!(plots.drops, vec![[0., 0.], [3., 0.]]);
+    }
+}
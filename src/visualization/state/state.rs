@@ -1,358 +1,677 @@
-use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet, VecDeque},
-    f32::consts::PI,
-    rc::Rc,
-};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 
-use egui::{Checkbox, Context, ScrollArea, Slider};
+use egui::{Checkbox, ComboBox, Context, ScrollArea, Slider};
 use macroquad::prelude::*;
 
-use crate::visualization::utilities::*;
-
+use super::coloring::*;
+use super::event_log::*;
+use super::latency::*;
 use super::local_message::*;
 use super::message::*;
 use super::node::*;
+use super::plots::*;
 use super::timer::*;
+use crate::visualization::{
+    batch::ShapeBatch, camera::Camera, config::config, layout::*, layout_store::*,
+    spatial::SpatialGrid, utilities::*,
+};
+use dslab_trace::{
+    arena::*,
+    trace::{Corruption, Event, EventKind, LocalMessageKind, MessageState, Trace},
+    Replay, SimTime,
+};
 
-#[derive(Clone, Debug)]
-pub enum StateEvent {
-    NodeStarted(String),
-    MessageSent(String),
-    LocalMessageSent(String),
-    LocalMessageReceived(String),
-    NodeConnected(String),
-    NodeDisconnected(String),
-    TimerSet(StateTimer),
-    LinkDisabled((String, String)),
-    LinkEnabled((String, String)),
-    DropIncoming(String),
-    PassIncoming(String),
-    DropOutgoing(String),
-    PassOutgoing(String),
-    NetworkPartition((Vec<String>, Vec<String>)),
-    NetworkReset(),
-    NodeStateUpdated((String, String)),
+#[derive(Clone, Default)]
+pub struct MsgTypeFilter {
+    pub ordered_types: Vec<String>,
+    pub counts: HashMap<String, usize>,
+    pub show_type: HashMap<String, bool>,
+    pub solo_type: Option<String>,
 }
 
-#[derive(Clone)]
-pub struct EventQueueItem {
-    time: f64,
-    event: StateEvent,
+impl MsgTypeFilter {
+    pub fn register(&mut self, tip: &str) {
+        if let Some(count) = self.counts.get_mut(tip) {
+            *count += 1;
+            return;
+        }
+        self.ordered_types.push(tip.to_owned());
+        self.counts.insert(tip.to_owned(), 1);
+        self.show_type.insert(tip.to_owned(), true);
+    }
+
+    pub fn is_shown(&self, tip: &str) -> bool {
+        match &self.solo_type {
+            Some(solo) => solo == tip,
+            None => *self.show_type.get(tip).unwrap_or(&true),
+        }
+    }
+
+    pub fn set_all(&mut self, show: bool) {
+        self.solo_type = None;
+        for shown in self.show_type.values_mut() {
+            *shown = show;
+        }
+    }
+
+    pub fn shown_count(&self) -> usize {
+        self.counts
+            .iter()
+            .filter(|(tip, _)| self.is_shown(tip))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn total_count(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// The types are checked against the trace before the viewer starts.
+    pub fn show_only(&mut self, types: &[String]) {
+        for (tip, shown) in &mut self.show_type {
+            *shown = types.contains(tip);
+        }
+    }
+}
+
+/// Nodes and message types hidden by the filters. Windows that count messages remember
+/// the filters they counted for and count again when the filters change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HiddenByFilters {
+    pub nodes: Vec<NodeId>,
+    pub types: Vec<String>,
+}
+
+/// What a click in a window asks for. Windows only read the state while they are drawn,
+/// so the actions are applied after that, see `State::apply_ui_action`.
+#[derive(Clone, Copy, Debug)]
+pub enum UiAction {
+    OpenMessage(MessageId),
+    /// Opens the window of the node and highlights it.
+    OpenNode(NodeId),
+    Seek(SimTime),
+}
+
+impl UiAction {
+    /// Adds a link that queues the action when clicked.
+    pub fn link(self, ui: &mut egui::Ui, text: String, actions: &mut Vec<UiAction>) {
+        if ui.link(text).clicked() {
+            actions.push(self);
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct UIData {
-    pub ordered_nodes: Vec<String>,
-    pub show_events_for_node: HashMap<String, bool>,
-    pub show_node_windows: HashMap<String, bool>,
-    pub show_msg_windows: HashMap<String, bool>,
+    pub show_events_for_node: HashMap<NodeId, bool>,
+    pub msg_type_filter: MsgTypeFilter,
+    pub show_node_windows: HashMap<NodeId, bool>,
+    pub show_msg_windows: HashMap<MessageId, bool>,
     pub last_clicked: f64,
-    pub selected_node: Option<String>,
+    pub selected_node: Option<NodeId>,
+    /// World position of the mouse on the previous frame, used to drag nodes.
     pub selected_mouse_position: Vec2,
-    pub hovered_timer: Option<StateTimer>,
+    /// Screen position of the mouse on the previous frame while the canvas is dragged.
+    pub pan_origin: Option<Vec2>,
+    /// Set while the mouse is over an egui window, so clicks do not reach the canvas.
+    pub pointer_over_ui: bool,
+    pub hovered_timer: Option<TimerId>,
+    /// Node picked in a window and the time its highlight fades, see `HIGHLIGHT_DURATION`.
+    pub highlighted_node: Option<(NodeId, f64)>,
     pub show_timers: bool,
+    pub show_links: bool,
+    pub show_only_impaired_links: bool,
+    pub show_legend: bool,
+    pub show_events: bool,
+    pub show_plots: bool,
+    pub show_latency: bool,
+    pub lock_layout: bool,
+    /// Node and optional peer whose links are shown in the network configuration.
+    pub network_config_link: (Option<NodeId>, Option<NodeId>),
 }
 
 pub struct State {
-    pub nodes: HashMap<String, Rc<RefCell<StateNode>>>,
-    pub travelling_messages: HashMap<String, Rc<RefCell<StateMessage>>>,
-    pub messages: HashMap<String, Rc<RefCell<StateMessage>>>,
-    pub local_messages: HashMap<String, StateLocalMessage>,
-    pub event_queue: VecDeque<EventQueueItem>,
-    pub current_time: f64,
+    /// Path of the trace file, layouts are saved next to it.
+    pub trace_file: String,
+    /// The loaded trace, it is replayed from the start after seeking back.
+    pub trace: Trace,
+    /// Position of the playhead in the trace and the state of the system there.
+    pub replay: Replay,
+    pub nodes: Arena<NodeId, StateNode>,
+    pub messages: Arena<MessageId, StateMessage>,
+    pub travelling_messages: Vec<MessageId>,
+    pub local_messages: Arena<LocalMessageId, StateLocalMessage>,
+    pub timers: Arena<TimerId, StateTimer>,
+    /// Processed events and message deliveries for the Events window.
+    pub event_log: Vec<LoggedEvent>,
+    pub current_time: SimTime,
     pub last_updated: f64,
     pub paused: bool,
     pub global_speed: f32,
+    pub easing: Easing,
     pub ui_data: UIData,
+    pub msg_coloring: MsgColoring,
+    pub plots: Plots,
+    pub latency: LatencyStats,
     pub node_colors: VecDeque<Color>,
-    pub drop_outgoing: HashSet<String>,
-    pub drop_incoming: HashSet<String>,
-    pub disabled_links: HashSet<(String, String)>,
-    pub partition: Option<(Vec<String>, Vec<String>)>,
+    pub layout: LayoutKind,
+    /// Partition that the nodes are laid out for, see `apply_layout`.
+    pub laid_out_partition: Option<Vec<Vec<NodeId>>>,
+    /// World area that layouts place nodes in, the size of the window at startup.
+    pub layout_area: Rect,
+    pub camera: Camera,
+    /// World area visible on the last drawn frame, things outside of it are not drawn.
+    pub visible_area: Rect,
+    pub node_index: SpatialGrid<NodeId>,
+    pub msg_index: SpatialGrid<MessageId>,
     pub scale_coef: f32,
     pub start_time: f64,
 }
 
 impl State {
-    pub fn new() -> Self {
+    pub fn new(trace_file: String) -> Self {
         let start_time = get_time();
+        let protocol = config().protocol.clone().unwrap_or_else(|| {
+            std::path::Path::new(&trace_file)
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+        });
         Self {
-            nodes: HashMap::new(),
-            travelling_messages: HashMap::new(),
-            messages: HashMap::new(),
-            local_messages: HashMap::new(),
-            event_queue: VecDeque::new(),
-            current_time: 0.0,
+            trace_file,
+            trace: Trace::default(),
+            replay: Replay::new(&Trace::default()),
+            nodes: Arena::new(),
+            messages: Arena::new(),
+            travelling_messages: Vec::new(),
+            local_messages: Arena::new(),
+            timers: Arena::new(),
+            event_log: Vec::new(),
+            current_time: SimTime::ZERO,
             last_updated: 0.0,
             paused: false,
-            global_speed: DEFAULT_GLOBAL_SPEED,
+            global_speed: config().speed.unwrap_or(DEFAULT_GLOBAL_SPEED),
+            easing: config().message_easing,
             ui_data: UIData {
-                ordered_nodes: Vec::new(),
                 show_events_for_node: HashMap::new(),
+                msg_type_filter: MsgTypeFilter::default(),
                 show_node_windows: HashMap::new(),
                 show_msg_windows: HashMap::new(),
                 last_clicked: -1.,
                 selected_node: None,
                 selected_mouse_position: Vec2::new(0., 0.),
+                pan_origin: None,
+                pointer_over_ui: false,
                 hovered_timer: None,
+                highlighted_node: None,
                 show_timers: false,
+                show_links: true,
+                show_only_impaired_links: false,
+                show_legend: false,
+                show_events: false,
+                show_plots: false,
+                show_latency: false,
+                lock_layout: config().lock_layout,
+                network_config_link: (None, None),
             },
-            drop_outgoing: HashSet::new(),
-            drop_incoming: HashSet::new(),
-            disabled_links: HashSet::new(),
-            partition: None,
-            node_colors: VecDeque::from([
-                ORANGE, YELLOW, GREEN, SKYBLUE, BLUE, PURPLE, GOLD, LIGHTGRAY, PINK, LIME, VIOLET,
-                WHITE, MAGENTA,
-            ]),
-            scale_coef: 1.,
+            msg_coloring: MsgColoring::new(protocol),
+            plots: Plots::default(),
+            latency: LatencyStats::default(),
+            layout: config().layout,
+            laid_out_partition: None,
+            layout_area: Rect::new(
+                -screen_width() / 2.,
+                -screen_height() / 2.,
+                screen_width(),
+                screen_height(),
+            ),
+            camera: Camera::new(Vec2::ZERO),
+            visible_area: Rect::default(),
+            node_index: SpatialGrid::new(config().node_radius),
+            msg_index: SpatialGrid::new(config().message_radius),
+            node_colors: config()
+                .theme
+                .node_colors
+                .iter()
+                .map(|color| color.0)
+                .collect(),
+            scale_coef: config().scale,
             start_time,
         }
     }
 
-    pub fn process_node_started(&mut self, time: f64, name: String, id: u32, pos: Vec2) {
-        let color = self.node_colors.pop_front().unwrap_or(DEFAULT_NODE_COLOR);
-        let node = StateNode::new(name, id, pos, color);
-        self.ui_data
-            .show_events_for_node
-            .insert(node.name.clone(), true);
-        self.ui_data.ordered_nodes.push(node.name.clone());
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::NodeStarted(node.name.clone()),
-        });
-        self.nodes
-            .insert(node.name.clone(), Rc::new(RefCell::new(node)));
-    }
-
-    pub fn process_message_sent(
-        &mut self,
-        id: String,
-        time: f64,
-        src: &str,
-        dest: &str,
-        tip: String,
-        data: String,
-        duration: f32,
-        copies_received: u64,
-    ) {
-        if self.global_speed == DEFAULT_GLOBAL_SPEED && duration > 0. {
-            self.global_speed = duration / 10.;
-        }
-
-        let src_node = self.nodes.get(src).unwrap();
-        let msg = StateMessage::new(
-            id.clone(),
-            Rc::clone(src_node),
-            Rc::clone(self.nodes.get(dest).unwrap()),
-            tip,
-            data,
-            MessageStatus::Queued,
-            time as f32,
-            time as f32 + duration,
-            copies_received,
-        );
-        self.messages.insert(id.clone(), Rc::new(RefCell::new(msg)));
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::MessageSent(id),
-        });
+    /// The names are checked against the trace before the viewer starts.
+    pub fn show_events_only_for_nodes(&mut self, names: &[String]) {
+        let nodes: HashSet<NodeId> = names
+            .iter()
+            .filter_map(|name| self.nodes.id(name))
+            .collect();
+        for (node, shown) in &mut self.ui_data.show_events_for_node {
+            *shown = nodes.contains(node);
+        }
     }
 
-    pub fn process_local_message(
-        &mut self,
-        time: f64,
-        id: String,
-        node: String,
-        data: String,
-        is_sent: bool,
-    ) {
-        let msg_type: LocalMessageType;
-        let event: StateEvent;
-
-        if is_sent {
-            msg_type = LocalMessageType::Sent;
-            event = StateEvent::LocalMessageSent(id.clone());
-        } else {
-            msg_type = LocalMessageType::Received;
-            event = StateEvent::LocalMessageReceived(id.clone());
+    /// Creates nodes, messages, timers and local messages of the trace and starts its replay.
+    /// Entities are added in the order of the trace, so they keep their ids.
+    pub fn load_trace(&mut self, trace: Trace) {
+        for (id, node) in trace.nodes.iter() {
+            let pos = self.layout_area.center();
+            let color = self
+                .node_colors
+                .pop_front()
+                .unwrap_or(config().theme.default_node.0);
+            let name = trace.nodes.name(id).to_owned();
+            let id = self
+                .nodes
+                .insert_with(name, |id| StateNode::new(id, node.node_id, pos, color));
+            self.ui_data.show_events_for_node.insert(id, true);
         }
 
-        let msg = StateLocalMessage::new(id.clone(), time, node, data, msg_type);
-        self.local_messages.insert(id, msg);
+        let mean_latency = trace.mean_latency();
 
-        self.event_queue.push_back(EventQueueItem { time, event });
-    }
+        for (id, msg) in trace.messages.iter() {
+            let latency = msg.latency().unwrap_or(0.) as f32;
+            if config().speed.is_none() && self.global_speed == DEFAULT_GLOBAL_SPEED && latency > 0.
+            {
+                self.global_speed = latency / 10.;
+            }
+            self.ui_data.msg_type_filter.register(&msg.tip);
+            if latency > 0. {
+                self.msg_coloring.register_latency(latency);
+            }
+            let mut state_msg = StateMessage::new(
+                &self.nodes[msg.src],
+                msg.dest,
+                msg.tip.clone(),
+                prettify_json_string(msg.data.clone()),
+                msg.time_sent,
+                msg.deliveries.clone(),
+                msg.copies(),
+                msg.time_lost(mean_latency),
+            );
+            state_msg.corruption = msg.corruption.as_ref().map(|corruption| Corruption {
+                time: corruption.time,
+                original_data: prettify_json_string(corruption.original_data.clone()),
+                delivered_data: prettify_json_string(corruption.delivered_data.clone()),
+            });
+            state_msg.payload_diff = state_msg.corruption.as_ref().and_then(|corruption| {
+                diff_lines(&corruption.original_data, &corruption.delivered_data)
+            });
+            self.messages
+                .insert(trace.messages.name(id).to_owned(), state_msg);
+        }
+        self.msg_coloring
+            .assign_type_slots(&self.ui_data.msg_type_filter.ordered_types);
 
-    pub fn process_node_disconnected(&mut self, time: f64, node: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::NodeDisconnected(node),
-        });
+        for (id, msg) in trace.local_messages.iter() {
+            let data = match msg.kind {
+                LocalMessageKind::Sent => prettify_json_string(msg.data.clone()),
+                LocalMessageKind::Received => msg.data.clone(),
+            };
+            let local_msg = StateLocalMessage::new(msg.time, msg.node, msg.tip.clone(), data);
+            self.local_messages
+                .insert(trace.local_messages.name(id).to_owned(), local_msg);
+        }
+
+        for (id, timer) in trace.timers.iter() {
+            self.timers.insert(
+                trace.timers.name(id).to_owned(),
+                StateTimer::new(timer.clone()),
+            );
+        }
+
+        self.plots.load(&trace);
+        self.replay = Replay::new(&trace);
+        self.trace = trace;
     }
 
-    pub fn process_node_connected(&mut self, time: f64, node: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::NodeConnected(node),
-        });
+    /// Moves nodes and messages every frame, so that they follow layout changes and drags
+    /// while the replay is paused. Only the simulated time stops.
+    pub fn update(&mut self) {
+        self.check_keyboard_events();
+
+        let dt = get_frame_time();
+        for node in self.nodes.values_mut() {
+            node.animate(dt);
+        }
+
+        if !self.paused && get_time() - self.start_time >= 1. {
+            self.current_time += (get_time() - self.last_updated) * (self.global_speed as f64);
+            self.advance();
+        }
+        self.last_updated = get_time();
+        self.move_messages();
     }
 
-    pub fn process_timer_set(
-        &mut self,
-        id: String,
-        name: String,
-        time_set: f64,
-        node: String,
-        delay: f64,
-        time_removed: f64,
-    ) {
-        let timer = StateTimer::new(id, name, time_set, node, delay, time_removed);
-        self.event_queue.push_back(EventQueueItem {
-            time: time_set,
-            event: StateEvent::TimerSet(timer),
-        });
+    /// Moves the playhead to `time`. Seeking back replays the trace from the start.
+    pub fn seek(&mut self, time: SimTime) {
+        if time < self.current_time {
+            self.reset();
+        }
+        self.current_time = time;
+        self.advance();
+        // a jump delivers many messages at once, out of order
+        self.event_log.sort_by_key(|event| event.time);
+        for node in self.nodes.values_mut() {
+            node.messages_received.sort_by_key(|&(_, time)| time);
+        }
     }
 
-    pub fn process_link_disabled(&mut self, time: f64, from: String, to: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::LinkDisabled((from, to)),
-        });
+    /// Returns everything to the start of the trace. Nodes are laid out again
+    /// on the next advance only if the partition changes.
+    fn reset(&mut self) {
+        for node in self.nodes.values_mut() {
+            node.reset();
+        }
+        for msg in self.messages.values_mut() {
+            msg.reset();
+        }
+        for timer in self.timers.values_mut() {
+            timer.reset();
+        }
+        self.travelling_messages.clear();
+        self.event_log.clear();
+        self.replay = Replay::new(&self.trace);
     }
 
-    pub fn process_link_enabled(&mut self, time: f64, from: String, to: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::LinkEnabled((from, to)),
-        });
+    pub fn peek_event(&self) -> Option<&Event> {
+        self.replay.peek(&self.trace)
     }
 
-    pub fn process_drop_incoming(&mut self, time: f64, node: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::DropIncoming(node),
+    /// Replays the events up to the current time and updates the states of messages and timers.
+    /// Nodes are laid out once if the partition changed, however many events changed it.
+    fn advance(&mut self) {
+        // the trace is taken out for a while, so that its events are not cloned to be processed
+        let trace = std::mem::take(&mut self.trace);
+        for event in self.replay.advance(&trace, self.current_time) {
+            let logged = LoggedEvent::from_event(event.time, &event.kind, self);
+            self.event_log.push(logged);
+            self.process_event(&event.kind);
+        }
+        self.trace = trace;
+        if self.replay.network.partition != self.laid_out_partition {
+            self.apply_layout(true);
+        }
+
+        let messages = &mut self.messages;
+        let mut delivered = Vec::new();
+        let mut lost = Vec::new();
+        self.travelling_messages.retain(|&id| {
+            let msg = &mut messages[id];
+            for time in msg.update_status(self.current_time) {
+                delivered.push((msg.dest, id, time));
+            }
+            if matches!(msg.status, MessageState::Dropped(_)) {
+                lost.push((id, msg.flights[0].time_arrived));
+            }
+            matches!(msg.status, MessageState::InFlight | MessageState::Queued)
         });
+        for (dest, id, time) in delivered {
+            self.nodes[dest].messages_received.push((id, time));
+            let text = format!(
+                "message {} delivered to {}",
+                self.messages.name(id),
+                self.nodes.name(dest)
+            );
+            self.event_log.push(LoggedEvent::new(time, text, Some(id)));
+        }
+        for (id, time) in lost {
+            let text = format!("message {} lost", self.messages.name(id));
+            self.event_log.push(LoggedEvent::new(time, text, Some(id)));
+        }
+
+        for node in self.nodes.values_mut() {
+            node.update(self.current_time, &mut self.timers);
+        }
     }
 
-    pub fn process_pass_incoming(&mut self, time: f64, node: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::PassIncoming(node),
-        });
+    /// Places the travelling messages and the queues between and next to the current
+    /// positions of the nodes.
+    fn move_messages(&mut self) {
+        let nodes = &self.nodes;
+        for &id in &self.travelling_messages {
+            let msg = &mut self.messages[id];
+            let color = self.msg_coloring.color_for(msg, nodes);
+            msg.update(nodes, self.current_time, self.easing, color);
+        }
+        self.update_queues();
     }
 
-    pub fn process_drop_outgoing(&mut self, time: f64, node: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::DropOutgoing(node),
-        });
+    /// Collects the shown copies that wait at their destinations into the node queues,
+    /// oldest first, and places them in the stacks next to the nodes.
+    fn update_queues(&mut self) {
+        let mut queues: HashMap<NodeId, Vec<(MessageId, usize)>> = HashMap::new();
+        for &id in &self.travelling_messages {
+            let msg = &self.messages[id];
+            if self.show_message(msg) {
+                let queue = queues.entry(msg.dest).or_default();
+                queue.extend(msg.flights_queued().map(|(i, _)| (id, i)));
+            }
+        }
+        let (node_radius, msg_radius) = (self.get_node_radius(), self.get_msg_radius());
+        let timer_reach = 2. * self.get_timer_radius() + 5.;
+        for node in self.nodes.values_mut() {
+            let mut queue = queues.remove(&node.id).unwrap_or_default();
+            let messages = &mut self.messages;
+            queue.sort_by_key(|&(id, i)| messages[id].flights[i].time_arrived);
+            for (k, &(id, i)) in queue.iter().enumerate() {
+                let slot = k.min(MAX_SHOWN_QUEUED_MESSAGES - 1);
+                messages[id].flights[i].pos =
+                    node.queue_slot_pos(slot, node_radius + timer_reach, msg_radius);
+            }
+            node.queue = queue;
+        }
     }
 
-    pub fn process_pass_outgoing(&mut self, time: f64, node: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::PassOutgoing(node),
-        });
+    pub fn draw(&mut self) {
+        self.camera.activate();
+        self.visible_area = self.camera.visible_rect();
+        self.rebuild_spatial_index();
+
+        if let Some(groups) = &self.replay.network.partition {
+            for region in partition_regions(self.layout_area, groups.len()) {
+                draw_rectangle_lines(
+                    region.x + PARTITION_REGION_MARGIN,
+                    region.y + PARTITION_REGION_MARGIN,
+                    region.w - 2. * PARTITION_REGION_MARGIN,
+                    region.h - 2. * PARTITION_REGION_MARGIN,
+                    3.,
+                    config().theme.partition_line.0,
+                );
+            }
+        }
+
+        let mut batch = ShapeBatch::new();
+        if self.ui_data.show_links {
+            self.draw_links(&mut batch);
+        }
+        let messages: Vec<_> = self
+            .travelling_messages
+            .iter()
+            .map(|&msg| &self.messages[msg])
+            .filter(|msg| self.show_message(msg))
+            .collect();
+        let draw_flows = messages.len() > config().max_drawn_messages;
+        if draw_flows {
+            self.draw_flows(&messages, &mut batch);
+        }
+
+        let node_reach = self.get_node_radius() + 2. * self.get_timer_radius() + 5.;
+        let nodes: Vec<_> = self
+            .nodes
+            .values()
+            .filter(|node| self.is_visible(node.get_pos(), node_reach))
+            .collect();
+        let messages: Vec<_> = messages.into_iter().filter(|_| !draw_flows).collect();
+        for node in &nodes {
+            node.draw(self, &mut batch);
+        }
+        for msg in &messages {
+            msg.draw(self, &mut batch);
+        }
+        for node in &nodes {
+            node.draw_queue(self, &mut batch);
+        }
+        batch.flush();
+
+        if self.is_label_visible(self.get_node_radius()) {
+            for node in &nodes {
+                node.draw_label(self);
+                node.draw_badges(self);
+            }
+        }
+        if self.is_label_visible(self.get_msg_radius()) {
+            for node in &nodes {
+                node.draw_queue_label(self);
+            }
+        }
+        if self.is_label_visible(self.get_msg_radius()) {
+            for msg in &messages {
+                msg.draw_label(self);
+            }
+        }
+        set_default_camera();
+        self.draw_time();
     }
 
-    pub fn process_network_partition(
-        &mut self,
-        time: f64,
-        group1: Vec<String>,
-        group2: Vec<String>,
-    ) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::NetworkPartition((group1, group2)),
-        });
+    /// Fills the spatial indexes used to find nodes and messages under the cursor.
+    pub fn rebuild_spatial_index(&mut self) {
+        self.node_index.reset(self.get_node_radius() * 4.);
+        for node in self.nodes.values() {
+            self.node_index.insert(node.id, node.get_pos());
+        }
+        self.msg_index.reset(self.get_msg_radius() * 4.);
+        let shown: Vec<_> = self
+            .travelling_messages
+            .iter()
+            .map(|&id| (id, &self.messages[id]))
+            .filter(|(_, msg)| self.show_message(msg))
+            .flat_map(|(id, msg)| {
+                msg.flights_in_air()
+                    .map(move |(_, flight)| (id, flight.pos))
+            })
+            .collect();
+        if shown.len() <= config().max_drawn_messages {
+            for (id, pos) in shown {
+                self.msg_index.insert(id, pos);
+            }
+        }
+        for node in self.nodes.values() {
+            for &(id, i) in node.queue.iter().take(MAX_SHOWN_QUEUED_MESSAGES) {
+                self.msg_index.insert(id, self.messages[id].flights[i].pos);
+            }
+        }
     }
 
-    pub fn process_network_reset(&mut self, time: f64) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::NetworkReset(),
-        });
+    pub fn is_visible(&self, pos: Vec2, radius: f32) -> bool {
+        pos.x + radius >= self.visible_area.left()
+            && pos.x - radius <= self.visible_area.right()
+            && pos.y + radius >= self.visible_area.top()
+            && pos.y - radius <= self.visible_area.bottom()
     }
 
-    pub fn process_state_updated(&mut self, time: f64, node: String, node_state: String) {
-        self.event_queue.push_back(EventQueueItem {
-            time,
-            event: StateEvent::NodeStateUpdated((node, node_state)),
-        });
+    fn is_label_visible(&self, radius: f32) -> bool {
+        radius * 2. * self.camera.zoom >= LABEL_MIN_SCREEN_SIZE
     }
 
-    pub fn update(&mut self) {
-        self.check_keyboard_events();
+    /// Number of segments for a circle of the given world radius at the current zoom.
+    pub fn circle_segments(&self, radius: f32) -> usize {
+        ShapeBatch::segments_for(radius * self.camera.zoom)
+    }
 
-        if self.paused || get_time() - self.start_time < 1. {
-            self.last_updated = get_time();
-            return;
-        } else {
-            self.current_time += (get_time() - self.last_updated) * (self.global_speed as f64);
-            self.last_updated = get_time();
+    /// Draws messages in flight between each pair of nodes as one line
+    /// whose thickness grows with the number of messages.
+    pub fn draw_flows(&self, messages: &[&StateMessage], batch: &mut ShapeBatch) {
+        let mut flows: HashMap<(NodeId, NodeId), usize> = HashMap::new();
+        for msg in messages
+            .iter()
+            .filter(|msg| msg.status == MessageState::InFlight)
+        {
+            let key = (msg.src.min(msg.dest), msg.src.max(msg.dest));
+            *flows.entry(key).or_insert(0) += 1;
         }
-
-        while let Some(event) = self.event_queue.front() {
-            if self.process_event(event.time, event.event.clone()) {
-                self.event_queue.pop_front();
-            } else {
-                break;
-            }
+        let max_thickness = self.get_node_radius() * 2.;
+        for ((a, b), count) in flows {
+            let from = self.nodes[a].get_pos();
+            let to = self.nodes[b].get_pos();
+            let thickness = f32::min(self.get_msg_radius() * (count as f32).sqrt(), max_thickness);
+            batch.line(from, to, thickness, config().theme.flow.0);
         }
+    }
 
-        self.travelling_messages.retain(|id, msg_ref| {
-            let mut msg = msg_ref.borrow_mut();
-            msg.update(self.global_speed, self.current_time as f32);
-            msg.update_status(self.current_time as f32);
-            if msg.status == MessageStatus::Delivered {
-                for _ in 0..msg.copies_received {
-                    msg.dest.borrow_mut().messages_received.push(id.clone());
+    /// Draws a line for every pair of started nodes. Disabled links are red and dashed:
+    /// a one-way disabled link has an arrow showing the blocked direction.
+    /// Links are impaired if they are disabled or one of their nodes drops messages.
+    /// When there are too many nodes, only impaired links are drawn.
+    pub fn draw_links(&self, batch: &mut ShapeBatch) {
+        let radius = self.get_node_radius();
+        let theme = &config().theme;
+        let nodes: Vec<_> = self.nodes.values().filter(|node| node.show).collect();
+        let only_impaired =
+            self.ui_data.show_only_impaired_links || nodes.len() > config().max_full_mesh_nodes;
+        let pairs: Vec<(usize, usize)> = if only_impaired {
+            let ids: Vec<NodeId> = nodes.iter().map(|node| node.id).collect();
+            self.impaired_pairs(&ids)
+        } else {
+            (0..nodes.len())
+                .flat_map(|i| ((i + 1)..nodes.len()).map(move |j| (i, j)))
+                .collect()
+        };
+        for (i, j) in pairs {
+            let (a, b) = (&nodes[i], &nodes[j]);
+            let forward = self.replay.network.disabled_links.contains(&(a.id, b.id));
+            let backward = self.replay.network.disabled_links.contains(&(b.id, a.id));
+            let (from, to) = (a.get_pos(), b.get_pos());
+            let direction = (to - from).normalize_or_zero();
+            let start = from + direction * radius;
+            let end = to - direction * radius;
+            match (forward, backward) {
+                (false, false) => {
+                    batch.line(start, end, 1., theme.link.0);
+                }
+                (true, true) => {
+                    batch.dashed_line(start, end, 3., 8., theme.disabled_link.0);
+                }
+                (true, false) | (false, true) => {
+                    batch.dashed_line(start, end, 1.5, 8., theme.disabled_link.0);
+                    let blocked = if forward { direction } else { -direction };
+                    let tip = (start + end) / 2. + blocked * radius * 0.5;
+                    batch.arrow_head(tip, blocked, radius * 0.7, theme.disabled_link.0);
                 }
-                false
-            } else if msg.status == MessageStatus::Dropped {
-                false
-            } else {
-                true
             }
-        });
-
-        for (_, node) in &mut self.nodes {
-            node.borrow_mut().update(self.current_time);
         }
     }
 
-    pub fn draw(&mut self) {
-        if self.partition.is_some() {
-            let start = (screen_width() / 2., 0.);
-            let end = (screen_width() / 2., screen_height());
-            draw_line(start.0, start.1, end.0, end.1, 5., LIGHTGRAY);
-        }
-        for (_, node) in &self.nodes {
-            node.borrow().draw(&self);
+    /// Pairs of indexes into `nodes` whose links are disabled or touch a node that drops messages.
+    pub fn impaired_pairs(&self, nodes: &[NodeId]) -> Vec<(usize, usize)> {
+        let index: HashMap<&NodeId, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node, i))
+            .collect();
+        let ordered = |a: usize, b: usize| (a.min(b), a.max(b));
+        let mut pairs = BTreeSet::new();
+        for (from, to) in &self.replay.network.disabled_links {
+            if let (Some(&a), Some(&b)) = (index.get(from), index.get(to)) {
+                pairs.insert(ordered(a, b));
+            }
         }
-        for (_, msg) in &self.travelling_messages {
-            let msg_borrowed = msg.borrow();
-            let src = &msg_borrowed.src.borrow().name.clone();
-            let dest = &msg_borrowed.dest.borrow().name.clone();
-            let show_message = *self.ui_data.show_events_for_node.get(src).unwrap()
-                && *self.ui_data.show_events_for_node.get(dest).unwrap();
-            if show_message {
-                msg_borrowed.draw(&self);
+        let network = &self.replay.network;
+        for node in network.drop_incoming.iter().chain(&network.drop_outgoing) {
+            if let Some(&a) = index.get(node) {
+                pairs.extend((0..nodes.len()).filter(|&b| b != a).map(|b| ordered(a, b)));
             }
         }
-        self.draw_time();
+        pairs.into_iter().collect()
     }
 
     pub fn draw_time(&self) {
         draw_text_ex(
-            &format!("Time: {:.5}", self.current_time),
+            &format!("Time: {}", format_time(self.current_time)),
             screen_width() * 0.03,
             screen_height() * 0.96,
             TextParams {
                 font_size: (screen_width() / 18.0).floor() as u16,
-                color: WHITE,
+                color: config().theme.text.0,
                 ..Default::default()
             },
         );
@@ -360,171 +679,433 @@ impl State {
 
     pub fn check_keyboard_events(&mut self) {
         let abs_time = get_time();
-        if is_key_pressed(KeyCode::Space) {
+        let keys = &config().keybindings;
+        if keys.pause.is_pressed() {
             self.paused = !self.paused;
         }
-        if is_key_pressed(KeyCode::Right) && !self.event_queue.is_empty() {
-            let new_current_time = self.event_queue.front().unwrap().time - 0.01;
-            let delta = self.current_time - new_current_time;
-            for (_, msg) in &self.travelling_messages {
-                msg.borrow_mut().update_with_jump(
-                    self.global_speed,
-                    self.current_time as f32,
-                    delta as f32,
-                );
+        if keys.next_event.is_pressed() {
+            if let Some(event) = self.peek_event() {
+                self.current_time = self.current_time.max(event.time - 0.01);
             }
-            self.current_time = new_current_time;
         }
-        if is_key_down(KeyCode::KpAdd) || is_key_down(KeyCode::Equal) {
-            self.scale_coef += SCALE_COEF_DELTA;
+        let screen_center = Vec2::new(screen_width() / 2., screen_height() / 2.);
+        if keys.zoom_in.is_down() {
+            self.camera
+                .zoom_at(screen_center, 1. + config().scale_delta);
         }
-        if is_key_down(KeyCode::Minus) || is_key_down(KeyCode::KpSubtract) {
-            self.scale_coef = f32::max(0.0, self.scale_coef - SCALE_COEF_DELTA);
+        if keys.zoom_out.is_down() {
+            self.camera
+                .zoom_at(screen_center, 1. / (1. + config().scale_delta));
         }
-        if is_key_down(KeyCode::Up) {
-            self.global_speed += GLOBAL_SPEED_DELTA;
+        if keys.fit_to_content.is_pressed() {
+            self.fit_to_content();
         }
-        if is_key_down(KeyCode::Down) {
-            self.global_speed = f32::max(0.0, self.global_speed - GLOBAL_SPEED_DELTA);
+        if keys.speed_up.is_down() {
+            self.global_speed += config().speed_delta;
         }
-        if is_mouse_button_down(MouseButton::Left) {
-            let mouse_pos = mouse_position();
-            if self.ui_data.selected_node.is_none() {
-                if let Some(node) = self.get_node_by_mouse_pos(mouse_pos) {
-                    self.ui_data.selected_node = Some(node);
-                    self.ui_data.selected_mouse_position = Vec2::new(mouse_pos.0, mouse_pos.1);
-                }
+        if keys.speed_down.is_down() {
+            self.global_speed = f32::max(0.0, self.global_speed - config().speed_delta);
+        }
+        let screen_mouse_pos = Vec2::from(mouse_position());
+        let mouse_pos = self.camera.screen_to_world(screen_mouse_pos);
+        let on_canvas = !self.ui_data.pointer_over_ui;
+        let (_, wheel) = mouse_wheel();
+        if on_canvas && wheel != 0. {
+            let factor = if wheel > 0. {
+                ZOOM_WHEEL_FACTOR
             } else {
-                let node = self.ui_data.selected_node.clone().unwrap();
-                let node = self.nodes.get_mut(&node).unwrap();
-                let drag_direction =
-                    Vec2::new(mouse_pos.0, mouse_pos.1) - self.ui_data.selected_mouse_position;
-                if !drag_direction.is_nan() {
-                    let new_pos = node.borrow().get_pos() + drag_direction;
-                    node.borrow_mut().update_pos(new_pos);
-                }
-                self.ui_data.selected_mouse_position = Vec2::new(mouse_pos.0, mouse_pos.1);
-            }
-
-            if let Some(msg_id) = self.get_msg_by_mouse_pos(mouse_pos) {
+                1. / ZOOM_WHEEL_FACTOR
+            };
+            self.camera.zoom_at(screen_mouse_pos, factor);
+        }
+        if on_canvas && is_mouse_button_pressed(MouseButton::Left) {
+            self.ui_data.last_clicked = abs_time;
+            if let Some(node) = self.get_node_by_mouse_pos(mouse_pos) {
+                self.ui_data.selected_node = Some(node);
+                self.ui_data.selected_mouse_position = mouse_pos;
+            } else if let Some(msg_id) = self.get_msg_by_mouse_pos(mouse_pos) {
                 self.ui_data.show_msg_windows.insert(msg_id, true);
+            } else {
+                self.ui_data.pan_origin = Some(screen_mouse_pos);
             }
         }
-        if is_mouse_button_pressed(MouseButton::Left) {
-            self.ui_data.last_clicked = abs_time;
+        if is_mouse_button_down(MouseButton::Left) {
+            if let Some(node) = self.ui_data.selected_node {
+                let node = &mut self.nodes[node];
+                let drag_direction = mouse_pos - self.ui_data.selected_mouse_position;
+                if !drag_direction.is_nan() {
+                    let new_pos = node.get_pos() + drag_direction;
+                    node.update_pos(new_pos);
+                }
+                self.ui_data.selected_mouse_position = mouse_pos;
+            } else if let Some(origin) = self.ui_data.pan_origin {
+                self.camera.pan_by_screen_delta(screen_mouse_pos - origin);
+                self.ui_data.pan_origin = Some(screen_mouse_pos);
+            }
         }
         if !is_mouse_button_pressed(MouseButton::Left) && !is_mouse_button_down(MouseButton::Left) {
-            if abs_time - self.ui_data.last_clicked <= SINGLE_CLICK_DELAY
-                && self.ui_data.selected_node.is_some()
-            {
-                self.ui_data
-                    .show_node_windows
-                    .insert(self.ui_data.selected_node.clone().unwrap(), true);
+            self.ui_data.pan_origin = None;
+            if let Some(selected) = self.ui_data.selected_node.take() {
+                if abs_time - self.ui_data.last_clicked <= SINGLE_CLICK_DELAY {
+                    self.ui_data.show_node_windows.insert(selected, true);
+                } else {
+                    self.nodes[selected].pinned = true;
+                    self.save_layout();
+                }
             }
-            self.ui_data.selected_node = None;
         }
-        if self.ui_data.show_timers {
+        if self.ui_data.show_timers && on_canvas {
             let node_radius = self.get_node_radius();
             let timer_radius = self.get_timer_radius();
-            for (_, node) in &self.nodes {
-                let hovered_timer = node
-                    .borrow()
-                    .check_for_hovered_timer(node_radius, timer_radius);
+            let reach = node_radius + 2. * timer_radius + 5.;
+            if let Some(node) = self.node_index.nearest(mouse_pos, reach) {
+                let hovered_timer = self.nodes[node].check_for_hovered_timer(
+                    mouse_pos,
+                    node_radius,
+                    timer_radius,
+                    &self.timers,
+                );
                 if hovered_timer.is_some() {
                     self.ui_data.hovered_timer = hovered_timer;
-                    break;
                 }
             }
         }
     }
 
-    pub fn get_msg_by_mouse_pos(&mut self, mouse_pos: (f32, f32)) -> Option<String> {
-        for (_, msg) in &self.travelling_messages {
-            if calc_dist(Vec2::new(mouse_pos.0, mouse_pos.1), msg.borrow().get_pos())
-                < self.get_msg_radius()
-            {
-                return Some(msg.borrow().id.clone());
-            }
-        }
-        None
+    pub fn hidden_by_filters(&self) -> HiddenByFilters {
+        let mut nodes: Vec<NodeId> = self
+            .ui_data
+            .show_events_for_node
+            .iter()
+            .filter(|(_, &shown)| !shown)
+            .map(|(&node, _)| node)
+            .collect();
+        nodes.sort();
+        let filter = &self.ui_data.msg_type_filter;
+        let types = filter
+            .ordered_types
+            .iter()
+            .filter(|tip| !filter.is_shown(tip))
+            .cloned()
+            .collect();
+        HiddenByFilters { nodes, types }
     }
 
-    pub fn get_node_by_mouse_pos(&mut self, mouse_pos: (f32, f32)) -> Option<String> {
-        for (_, node) in &self.nodes {
-            if calc_dist(Vec2::new(mouse_pos.0, mouse_pos.1), node.borrow().get_pos())
-                < self.get_node_radius()
-            {
-                return Some(node.borrow().name.clone());
-            }
-        }
-        None
+    pub fn show_message(&self, msg: &StateMessage) -> bool {
+        self.ui_data.show_events_for_node[&msg.src]
+            && self.ui_data.show_events_for_node[&msg.dest]
+            && self.ui_data.msg_type_filter.is_shown(&msg.tip)
+    }
+
+    /// Lines about messages follow `show_message`, other lines are hidden with their nodes.
+    pub fn show_logged_event(&self, event: &LoggedEvent) -> bool {
+        event
+            .msg
+            .is_none_or(|msg| self.show_message(&self.messages[msg]))
+            && event
+                .nodes
+                .iter()
+                .all(|node| self.ui_data.show_events_for_node[node])
+    }
+
+    pub fn get_msg_by_mouse_pos(&self, mouse_pos: Vec2) -> Option<MessageId> {
+        self.msg_index.nearest(mouse_pos, self.get_msg_radius())
+    }
+
+    pub fn get_node_by_mouse_pos(&self, mouse_pos: Vec2) -> Option<NodeId> {
+        self.node_index.nearest(mouse_pos, self.get_node_radius())
     }
 
     pub fn draw_ui(&mut self) {
         egui_macroquad::ui(|egui_ctx| {
+            self.ui_data.pointer_over_ui =
+                egui_ctx.wants_pointer_input() || egui_ctx.is_pointer_over_area();
+            self.draw_ui_plots(egui_ctx);
             self.draw_ui_config_window(egui_ctx);
             self.draw_ui_hovered_timer(egui_ctx);
             self.draw_ui_node_windows(egui_ctx);
             self.draw_ui_msg_windows(egui_ctx);
             self.draw_ui_network_window(egui_ctx);
+            self.draw_ui_legend(egui_ctx);
+            self.draw_ui_events_window(egui_ctx);
+            self.draw_ui_latency_window(egui_ctx);
         });
     }
 
     pub fn draw_ui_config_window(&mut self, egui_ctx: &Context) {
         egui::Window::new("Config").show(egui_ctx, |ui| {
-            let next_event_at;
-            if self.event_queue.is_empty() {
-                next_event_at = "--".to_owned();
-            } else {
-                next_event_at = format!("{:.4}", self.event_queue.front().unwrap().time);
-            }
+            let next_event_at = match self.peek_event() {
+                Some(event) => format_time(event.time),
+                None => "--".to_owned(),
+            };
             ui.label(format!("Next event at: {}", next_event_at));
+            ui.label(format!(
+                "Frame time: {:.1} ms ({} FPS)",
+                get_frame_time() * 1000.,
+                get_fps()
+            ));
             ui.add(Checkbox::new(&mut self.ui_data.show_timers, "Show timers"));
+            ui.horizontal(|ui| {
+                ui.add(Checkbox::new(&mut self.ui_data.show_links, "Show links"));
+                ui.add_enabled(
+                    self.ui_data.show_links,
+                    Checkbox::new(&mut self.ui_data.show_only_impaired_links, "Only impaired"),
+                );
+            });
+            self.msg_coloring.draw_ui_mode_selector(ui);
+            ComboBox::from_label("Easing")
+                .selected_text(self.easing.name())
+                .show_ui(ui, |ui| {
+                    for easing in Easing::ALL {
+                        ui.selectable_value(&mut self.easing, easing, easing.name());
+                    }
+                });
+            ui.add(Checkbox::new(&mut self.ui_data.show_legend, "Show legend"));
+            ui.add(Checkbox::new(&mut self.ui_data.show_events, "Show events"));
+            ui.add(Checkbox::new(&mut self.ui_data.show_plots, "Show plots"));
+            ui.add(Checkbox::new(
+                &mut self.ui_data.show_latency,
+                "Show latency",
+            ));
+            let mut layout = self.layout;
+            ComboBox::from_label("Layout")
+                .selected_text(layout.name())
+                .show_ui(ui, |ui| {
+                    for kind in LayoutKind::ALL {
+                        ui.selectable_value(&mut layout, kind, kind.name());
+                    }
+                });
+            if layout != self.layout {
+                self.layout = layout;
+                self.apply_layout(true);
+            }
+            ui.horizontal(|ui| {
+                ui.add(Checkbox::new(&mut self.ui_data.lock_layout, "Lock layout"))
+                    .on_hover_text("Network resets and partitions do not move pinned nodes");
+                if ui.button("Unpin all").clicked() {
+                    for node in self.nodes.values_mut() {
+                        node.pinned = false;
+                    }
+                    self.save_layout();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Fit to content").clicked() {
+                    self.fit_to_content();
+                }
+                ui.label(format!("Zoom: {:.0}%", self.camera.zoom * 100.));
+            });
+            ui.add(Slider::new(&mut self.scale_coef, 0.1..=5.).text("Node size"));
             ui.add(
                 Slider::new(&mut self.global_speed, 0.0000..=1.)
                     .logarithmic(true)
-                    .step_by(GLOBAL_SPEED_DELTA as f64)
+                    .step_by(config().speed_delta as f64)
                     .text("Speed"),
             );
             ui.collapsing("Show events (messages and timers) for a node:", |ui| {
                 ui.set_max_height(screen_height() * 0.2);
                 ScrollArea::vertical().show(ui, |ui| {
-                    for node in &self.ui_data.ordered_nodes {
-                        let show_events = self.ui_data.show_events_for_node.get_mut(node).unwrap();
-                        let node = self.nodes.get(node).unwrap().borrow().name.clone();
-                        ui.add(Checkbox::new(show_events, format!("Node {}", node)));
+                    for node in self.nodes.ids() {
+                        let show_events = self.ui_data.show_events_for_node.get_mut(&node).unwrap();
+                        let name = self.nodes.name(node);
+                        ui.add(Checkbox::new(show_events, format!("Node {}", name)));
                     }
                 });
                 ui.set_max_height(f32::INFINITY);
             });
+            ui.collapsing("Show messages of a type:", |ui| {
+                let filter = &mut self.ui_data.msg_type_filter;
+                ui.label(format!(
+                    "Messages shown: {} of {}",
+                    filter.shown_count(),
+                    filter.total_count()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Show all").clicked() {
+                        filter.set_all(true);
+                    }
+                    if ui.button("Hide all").clicked() {
+                        filter.set_all(false);
+                    }
+                });
+                ui.set_max_height(screen_height() * 0.2);
+                ScrollArea::vertical().show(ui, |ui| {
+                    for tip in &filter.ordered_types {
+                        let count = filter.counts.get(tip).unwrap();
+                        ui.horizontal(|ui| {
+                            let is_solo = filter.solo_type.as_ref() == Some(tip);
+                            if ui.selectable_label(is_solo, "Solo").clicked() {
+                                filter.solo_type = if is_solo { None } else { Some(tip.clone()) };
+                            }
+                            let show = filter.show_type.get_mut(tip).unwrap();
+                            ui.add_enabled(
+                                filter.solo_type.is_none(),
+                                Checkbox::new(show, format!("{} ({})", tip, count)),
+                            );
+                        });
+                    }
+                });
+                ui.set_max_height(f32::INFINITY);
+            });
+        });
+    }
+
+    /// Shows the plots panel at the bottom, a click on a plot seeks to its time.
+    pub fn draw_ui_plots(&mut self, egui_ctx: &Context) {
+        if !self.ui_data.show_plots {
+            return;
+        }
+        let mut plots = std::mem::take(&mut self.plots);
+        plots.update_counts(self.hidden_by_filters(), |msg| {
+            self.show_message(&self.messages[msg])
         });
+        self.plots = plots;
+        let clicked =
+            self.plots
+                .draw_ui(egui_ctx, &self.trace.events, &self.nodes, self.current_time);
+        if let Some(time) = clicked {
+            self.seek(time);
+        }
+    }
+
+    pub fn draw_ui_latency_window(&mut self, egui_ctx: &Context) {
+        if !self.ui_data.show_latency {
+            return;
+        }
+        // the stats are taken out for a while, so that the filter check can borrow the state
+        let mut latency = std::mem::take(&mut self.latency);
+        latency.update(
+            &self.messages,
+            &self.nodes,
+            self.hidden_by_filters(),
+            |msg| self.show_message(msg),
+        );
+        self.latency = latency;
+        let actions = self.latency.draw_ui(
+            egui_ctx,
+            &mut self.ui_data.show_latency,
+            &self.messages,
+            &self.nodes,
+        );
+        for action in actions {
+            self.apply_ui_action(action);
+        }
+    }
+
+    pub fn draw_ui_legend(&mut self, egui_ctx: &Context) {
+        if !self.ui_data.show_legend {
+            return;
+        }
+        let nodes: Vec<(String, Color)> = self
+            .nodes
+            .iter()
+            .map(|(id, node)| (self.nodes.name(id).to_owned(), node.color))
+            .collect();
+        self.msg_coloring.draw_ui_legend(
+            egui_ctx,
+            &mut self.ui_data.show_legend,
+            &nodes,
+            &self.ui_data.msg_type_filter.ordered_types,
+        );
     }
 
     pub fn draw_ui_hovered_timer(&mut self, egui_ctx: &Context) {
-        if let Some(timer) = &self.ui_data.hovered_timer {
-            timer.draw_ui(egui_ctx);
+        if let Some(timer) = self.ui_data.hovered_timer.take() {
+            self.timers[timer].draw_ui(egui_ctx, self.timers.name(timer));
         }
-        self.ui_data.hovered_timer = None;
     }
 
     pub fn draw_ui_node_windows(&mut self, egui_ctx: &Context) {
-        for (node, show_window) in &mut self.ui_data.show_node_windows {
-            let node = self.nodes.get(node).unwrap().borrow();
-            node.draw_ui(egui_ctx, show_window, &self.messages);
+        let mut windows = std::mem::take(&mut self.ui_data.show_node_windows);
+        let mut actions = Vec::new();
+        for (&node, show_window) in &mut windows {
+            actions.extend(self.nodes[node].draw_ui(egui_ctx, show_window, self));
+        }
+        self.ui_data.show_node_windows = windows;
+        for action in actions {
+            self.apply_ui_action(action);
         }
     }
 
-    pub fn draw_ui_msg_windows(&mut self, egui_ctx: &Context) {
-        for (msg_id, show_window) in &mut self.ui_data.show_msg_windows {
-            if !self.travelling_messages.contains_key(msg_id) {
-                continue;
+    pub fn apply_ui_action(&mut self, action: UiAction) {
+        match action {
+            UiAction::OpenMessage(msg) => {
+                self.ui_data.show_msg_windows.insert(msg, true);
+            }
+            UiAction::OpenNode(node) => {
+                self.ui_data.show_node_windows.insert(node, true);
+                self.ui_data.highlighted_node = Some((node, get_time() + HIGHLIGHT_DURATION));
             }
-            let msg = self.travelling_messages.get(msg_id).unwrap().borrow();
+            UiAction::Seek(time) => self.seek(time),
+        }
+    }
 
-            msg.draw_ui(egui_ctx, show_window);
+    pub fn is_highlighted(&self, node: NodeId) -> bool {
+        self.ui_data
+            .highlighted_node
+            .is_some_and(|(highlighted, until)| highlighted == node && get_time() < until)
+    }
+
+    pub fn draw_ui_msg_windows(&mut self, egui_ctx: &Context) {
+        let mut windows = std::mem::take(&mut self.ui_data.show_msg_windows);
+        for (&msg, show_window) in &mut windows {
+            self.messages[msg].draw_ui(egui_ctx, show_window, self.messages.name(msg), self);
+        }
+        self.ui_data.show_msg_windows = windows;
+    }
+
+    /// Lists the processed events that pass the node and type filters, newest at the bottom.
+    /// Message events open the message window.
+    pub fn draw_ui_events_window(&mut self, egui_ctx: &Context) {
+        let mut show_window = self.ui_data.show_events;
+        let mut actions = Vec::new();
+        let shown: Vec<&LoggedEvent> = self
+            .event_log
+            .iter()
+            .filter(|event| self.show_logged_event(event))
+            .collect();
+        egui::Window::new(format!("Events ({})", shown.len()))
+            .id(egui::Id::new("Events"))
+            .open(&mut show_window)
+            .show(egui_ctx, |ui| {
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                ScrollArea::vertical()
+                    .max_height(screen_height() * 0.4)
+                    .stick_to_bottom(true)
+                    .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                        for event in &shown[rows] {
+                            ui.horizontal(|ui| {
+                                let time = format_time(event.time);
+                                UiAction::Seek(event.time).link(ui, time, &mut actions);
+                                match event.msg {
+                                    Some(msg) => {
+                                        let text = event.text.clone();
+                                        UiAction::OpenMessage(msg).link(ui, text, &mut actions);
+                                    }
+                                    None => {
+                                        ui.label(&event.text);
+                                    }
+                                }
+                            });
+                        }
+                    });
+            });
+        self.ui_data.show_events = show_window;
+        for action in actions {
+            self.apply_ui_action(action);
         }
     }
 
+    /// Names of the nodes for showing in the UI, in the order the nodes were started.
+    pub fn node_names<'a>(&self, nodes: impl IntoIterator<Item = &'a NodeId>) -> Vec<&str> {
+        let mut nodes: Vec<NodeId> = nodes.into_iter().copied().collect();
+        nodes.sort();
+        nodes
+            .into_iter()
+            .map(|node| self.nodes.name(node))
+            .collect()
+    }
+
     pub fn draw_ui_network_window(&mut self, egui_ctx: &Context) {
         egui::Window::new("Network")
             .default_pos((screen_width(), 15.))
@@ -532,168 +1113,247 @@ impl State {
                 ui.set_max_height(screen_height() * 0.5);
                 ScrollArea::vertical().show(ui, |ui| {
                     ui.strong("\nDrop incoming:");
-                    ui.label(format!("{:?}", self.drop_incoming));
+                    ui.label(format!(
+                        "{:?}",
+                        self.node_names(&self.replay.network.drop_incoming)
+                    ));
                     ui.strong("Drop outgoing:");
-                    ui.label(format!("{:?}", self.drop_outgoing));
+                    ui.label(format!(
+                        "{:?}",
+                        self.node_names(&self.replay.network.drop_outgoing)
+                    ));
                     ui.strong("Partition:");
-                    if self.partition.is_none() {
-                        ui.label("---");
-                    } else {
-                        let pair = self.partition.clone().unwrap();
-                        ui.label(format!("{:?} -x- {:?}", pair.0, pair.1));
+                    match &self.replay.network.partition {
+                        None => {
+                            ui.label("---");
+                        }
+                        Some(groups) => {
+                            let groups: Vec<String> = groups
+                                .iter()
+                                .map(|group| format!("{:?}", self.node_names(group)))
+                                .collect();
+                            ui.label(groups.join(" -x- "));
+                        }
                     }
                     ui.strong("Disabled links:");
-                    let mut shown: HashSet<(String, String)> = HashSet::new();
-                    for (from, to) in &self.disabled_links {
-                        let pair = (to.to_string(), from.to_string());
-                        if self.disabled_links.contains(&pair) && !shown.contains(&pair) {
+                    let mut shown: HashSet<(NodeId, NodeId)> = HashSet::new();
+                    let disabled_links = &self.replay.network.disabled_links;
+                    for &(from, to) in disabled_links {
+                        let (from_name, to_name) = (self.nodes.name(from), self.nodes.name(to));
+                        let pair = (to, from);
+                        if disabled_links.contains(&pair) && !shown.contains(&pair) {
                             shown.insert(pair);
-                            ui.label(format!("{} <-xx-> {}", from, to));
+                            ui.label(format!("{} <-xx-> {}", from_name, to_name));
                         } else {
-                            ui.label(format!("{} -xx-> {}", from, to));
+                            ui.label(format!("{} -xx-> {}", from_name, to_name));
                         }
                     }
+                    ui.strong("Configuration:");
+                    ui.label(format!(
+                        "All links: {}",
+                        format_network_params(&self.replay.network.params)
+                    ));
+                    self.draw_ui_link_config(ui);
                 });
                 ui.set_max_height(f32::INFINITY);
             });
     }
 
-    pub fn process_event(&mut self, time: f64, event: StateEvent) -> bool {
-        if self.current_time < time {
-            return false;
-        }
-        match event {
-            StateEvent::NodeStarted(node) => {
-                self.nodes.get_mut(&node).unwrap().borrow_mut().show = true;
-            }
-            StateEvent::MessageSent(msg_id) => {
-                let msg = self.messages.get(&msg_id).unwrap().clone();
-
-                msg.borrow()
-                    .src
-                    .borrow_mut()
-                    .messages_sent
-                    .push(msg_id.clone());
-                let src = msg.borrow().src.borrow().name.clone();
-                let dest = msg.borrow().dest.borrow().name.clone();
-                if self.drop_incoming.contains(&dest)
-                    || self.drop_incoming.contains(&src)
-                    || self.disabled_links.contains(&(src, dest))
-                {
-                    msg.borrow_mut().set_status(MessageStatus::Dropped);
-                } else {
-                    let start_pos = msg.borrow().src.borrow().get_pos();
-                    msg.borrow_mut().update_pos(start_pos);
-                    msg.borrow_mut().set_status(MessageStatus::OnTheWay);
-                    self.travelling_messages.insert(msg_id, msg);
+    /// Shows the parameters in effect for the links of the chosen node, or of the chosen pair.
+    fn draw_ui_link_config(&mut self, ui: &mut egui::Ui) {
+        let (mut node, mut peer) = self.ui_data.network_config_link;
+        let name = |id: Option<NodeId>, none: &str| {
+            id.map_or(none.to_string(), |id| self.nodes.name(id).to_string())
+        };
+        ComboBox::from_label("Node")
+            .selected_text(name(node, "---"))
+            .show_ui(ui, |ui| {
+                for id in self.nodes.ids() {
+                    ui.selectable_value(&mut node, Some(id), self.nodes.name(id));
                 }
+            });
+        let Some(node) = node else {
+            return;
+        };
+        if peer == Some(node) {
+            peer = None;
+        }
+        ComboBox::from_label("Peer")
+            .selected_text(name(peer, "all"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut peer, None, "all");
+                for id in self.nodes.ids().filter(|&id| id != node) {
+                    ui.selectable_value(&mut peer, Some(id), self.nodes.name(id));
+                }
+            });
+        let peers: Vec<NodeId> = match peer {
+            Some(peer) => vec![peer],
+            None => self.nodes.ids().filter(|&id| id != node).collect(),
+        };
+        for other in peers {
+            for (from, to) in [(node, other), (other, node)] {
+                ui.label(format!(
+                    "{} -> {}: {}",
+                    self.nodes.name(from),
+                    self.nodes.name(to),
+                    format_network_params(&self.replay.network.link_config(from, to))
+                ));
             }
-            StateEvent::NodeDisconnected(node) => {
-                self.nodes.get_mut(&node).unwrap().borrow_mut().connected = false
-            }
-            StateEvent::NodeConnected(node) => {
-                self.nodes.get_mut(&node).unwrap().borrow_mut().connected = true
+        }
+        self.ui_data.network_config_link = (Some(node), peer);
+    }
+
+    /// Shows an event that the replay applied. Decisions, like whether the network dropped
+    /// a message, are taken from the replay.
+    pub fn process_event(&mut self, event: &EventKind) {
+        match *event {
+            EventKind::NodeStarted(node)
+            | EventKind::NodeConnected(node)
+            | EventKind::NodeDisconnected(node) => {
+                let replayed = &self.replay.nodes[node.index()];
+                self.nodes[node].show = replayed.started;
+                self.nodes[node].connected = replayed.connected;
             }
-            StateEvent::TimerSet(timer) => self
-                .nodes
-                .get_mut(&timer.node)
-                .unwrap()
-                .borrow_mut()
-                .timers
-                .push_back(timer),
-            StateEvent::LocalMessageSent(id) => {
-                let msg = self.local_messages.remove(&id).unwrap();
-                self.nodes
-                    .get_mut(&msg.node)
-                    .unwrap()
-                    .borrow_mut()
-                    .local_messages_sent
-                    .push(msg);
-            }
-            StateEvent::LocalMessageReceived(id) => {
-                let msg = self.local_messages.remove(&id).unwrap();
-                self.nodes
-                    .get_mut(&msg.node)
-                    .unwrap()
-                    .borrow_mut()
-                    .local_messages_received
-                    .push(msg.clone());
-            }
-            StateEvent::LinkDisabled((from, to)) => {
-                self.disabled_links.insert((from, to));
-            }
-            StateEvent::LinkEnabled((from, to)) => {
-                self.disabled_links.remove(&(from, to));
-            }
-            StateEvent::DropIncoming(node) => {
-                self.drop_incoming.insert(node);
-            }
-            StateEvent::PassIncoming(node) => {
-                self.drop_incoming.remove(&node);
-            }
-            StateEvent::DropOutgoing(node) => {
-                self.drop_outgoing.insert(node);
-            }
-            StateEvent::PassOutgoing(node) => {
-                self.drop_outgoing.remove(&node);
-            }
-            StateEvent::NetworkPartition((group1, group2)) => {
-                for node1 in &group1 {
-                    for node2 in &group2 {
-                        self.disabled_links.insert((node1.clone(), node2.clone()));
-                        self.disabled_links.insert((node2.clone(), node1.clone()));
+            EventKind::MessageSent(id) => {
+                let src = self.messages[id].src;
+                self.nodes[src].messages_sent.push(id);
+                let msg = &mut self.messages[id];
+                match self.replay.drop_reason(id) {
+                    Some(reason) => msg.status = MessageState::Dropped(reason),
+                    None => {
+                        msg.status = MessageState::InFlight;
+                        self.travelling_messages.push(id);
                     }
                 }
-                self.partition = Some((group1, group2));
-                self.partition_nodes();
             }
-            StateEvent::NetworkReset() => {
-                self.partition = None;
-                self.drop_incoming.clear();
-                self.drop_outgoing.clear();
-                self.disabled_links.clear();
-                let center = Vec2::new(screen_width() / 2., screen_height() / 2.);
-                self.make_node_circle(self.ui_data.ordered_nodes.clone(), center, CIRCLE_RADIUS);
+            EventKind::TimerSet(timer) => {
+                let node = self.timers[timer].timer.node;
+                self.nodes[node].timers.push_back(timer);
             }
-            StateEvent::NodeStateUpdated((node, node_state)) => {
-                self.nodes.get_mut(&node).unwrap().borrow_mut().state = node_state;
+            EventKind::LocalMessageSent(id) => {
+                let node = self.local_messages[id].node;
+                self.nodes[node].local_messages_sent.push(id);
             }
+            EventKind::LocalMessageReceived(id) => {
+                let node = self.local_messages[id].node;
+                self.nodes[node].local_messages_received.push(id);
+            }
+            EventKind::NodeStateUpdated(node, ref node_state) => {
+                self.nodes[node].state.clone_from(node_state);
+                self.nodes[node].update_badges();
+            }
+            // network events only change the replayed network
+            _ => {}
         }
-        true
     }
 
-    pub fn partition_nodes(&mut self) {
-        if self.partition.is_none() {
-            return;
+    /// Arranges all nodes on the screen with the current layout,
+    /// each partitioned group in its own region.
+    pub fn apply_layout(&mut self, animate: bool) {
+        let area = self.layout_area;
+        self.laid_out_partition = self.replay.network.partition.clone();
+        match self.laid_out_partition.clone() {
+            None => {
+                let nodes: Vec<NodeId> = self.nodes.ids().collect();
+                self.place_nodes(&nodes, area, config().circle_radius, animate);
+            }
+            Some(groups) => {
+                let scale = config().partitioned_circle_radius;
+                for (group, region) in groups.iter().zip(partition_regions(area, groups.len())) {
+                    self.place_nodes(group, region, scale, animate);
+                }
+            }
         }
-        let (group1, group2) = self.partition.clone().unwrap();
-        let left = Vec2::new(screen_width() / 4., screen_height() / 2.);
-        let right = Vec2::new(screen_width() * 3. / 4., screen_height() / 2.);
-        self.make_node_circle(group1, left, PARTITIONED_CIRCLE_RADIUS);
-        self.make_node_circle(group2, right, PARTITIONED_CIRCLE_RADIUS);
     }
 
-    pub fn make_node_circle(&mut self, nodes: Vec<String>, center: Vec2, circle_radius: f32) {
-        for i in 0..nodes.len() {
-            let angle = (2.0 * PI / (nodes.len() as f32)) * (i as f32);
-            let pos = center + Vec2::from_angle(angle as f32) * circle_radius;
-            self.nodes
-                .get_mut(&nodes[i])
-                .unwrap()
-                .borrow_mut()
-                .update_pos(pos);
+    pub fn place_nodes(&mut self, nodes: &[NodeId], area: Rect, scale: f32, animate: bool) {
+        let positions = self
+            .layout
+            .engine()
+            .place(nodes, area, scale, &self.traffic());
+        for (node, pos) in positions {
+            let node = &mut self.nodes[node];
+            if self.ui_data.lock_layout && node.pinned {
+                continue;
+            }
+            if animate {
+                node.move_to(pos);
+            } else {
+                node.update_pos(pos);
+            }
+        }
+    }
+
+    pub fn traffic(&self) -> Traffic {
+        let mut traffic = Traffic::new();
+        for msg in self.messages.values() {
+            let key = (msg.src.min(msg.dest), msg.src.max(msg.dest));
+            *traffic.entry(key).or_insert(0) += 1;
+        }
+        traffic
+    }
+
+    pub fn restore_layout(&mut self) {
+        let names: Vec<String> = self
+            .nodes
+            .ids()
+            .map(|node| self.nodes.name(node).to_owned())
+            .collect();
+        let Some(layout) = load_layout(&self.trace_file, &names) else {
+            return;
+        };
+        for (name, saved) in layout.nodes {
+            if let Some(node) = self.nodes.id(&name) {
+                let node = &mut self.nodes[node];
+                node.update_pos(Vec2::from(saved.pos));
+                node.pinned = saved.pinned;
+            }
         }
     }
 
+    pub fn save_layout(&self) {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(id, node)| {
+                let saved = SavedNode {
+                    pos: node.get_pos().into(),
+                    pinned: node.pinned,
+                };
+                (self.nodes.name(id).to_owned(), saved)
+            })
+            .collect();
+        save_layout(&self.trace_file, &SavedLayout { nodes });
+    }
+
+    /// Moves the camera so that all started nodes are visible.
+    pub fn fit_to_content(&mut self) {
+        let mut positions = self
+            .nodes
+            .values()
+            .filter(|node| node.show)
+            .map(|node| node.target_pos.unwrap_or(node.pos));
+        let Some(first) = positions.next() else {
+            return;
+        };
+        let (min, max) = positions.fold((first, first), |(min, max), pos| {
+            (min.min(pos), max.max(pos))
+        });
+        let size = max - min;
+        let margin = FIT_MARGIN + self.get_node_radius();
+        self.camera
+            .fit(Rect::new(min.x, min.y, size.x, size.y), margin);
+    }
+
     pub fn get_node_radius(&self) -> f32 {
-        DEFAULT_NODE_RADIUS * self.scale_coef
+        config().node_radius * self.scale_coef
     }
 
     pub fn get_msg_radius(&self) -> f32 {
-        DEFAULT_MESSAGE_RADIUS * self.scale_coef
+        config().message_radius * self.scale_coef
     }
 
     pub fn get_timer_radius(&self) -> f32 {
-        DEFAULT_TIMER_RADIUS * self.scale_coef
+        config().timer_radius * self.scale_coef
     }
 }
@@ -3,85 +3,81 @@ use std::f32::consts::PI;
 use egui::Context;
 use macroquad::prelude::*;
 
-use crate::visualization::utilities::*;
+use crate::visualization::{batch::ShapeBatch, config::config, utilities::*};
+use dslab_trace::trace::Timer;
 
 use super::state::State;
 
+/// A timer of the trace and its slot around the node. Whether it is active
+/// is decided by `Timer::is_active_at`, as for `Trace::state_at`.
 #[derive(Debug, Clone)]
 pub struct StateTimer {
-    pub id: String,
-    pub name: String,
-    pub time_set: f64,
-    pub node: String,
-    pub delay: f64,
-    pub time_removed: f64,
+    pub timer: Timer,
     pub k: i32,
 }
 
 impl StateTimer {
-    pub fn new(
-        id: String,
-        name: String,
-        time_set: f64,
-        node: String,
-        delay: f64,
-        time_removed: f64,
-    ) -> Self {
-        Self {
-            id,
-            name,
-            time_set,
-            node,
-            delay,
-            time_removed,
-            k: -1,
-        }
+    pub fn new(timer: Timer) -> Self {
+        Self { timer, k: -1 }
+    }
+
+    /// Frees the slot of the timer before the trace is replayed.
+    pub fn reset(&mut self) {
+        self.k = -1;
     }
+
     pub fn get_position(&self, node_pos: Vec2, node_radius: f32, timer_radius: f32) -> Vec2 {
-        let angle = (2.0 * PI / (TIMERS_MAX_NUMBER as f32)) * (self.k as f32);
-        node_pos + Vec2::from_angle(angle as f32) * (node_radius + timer_radius + 5.)
+        let angle = (2.0 * PI / (config().timers_max_number as f32)) * (self.k as f32);
+        node_pos + Vec2::from_angle(angle) * (node_radius + timer_radius + 5.)
     }
 
-    pub fn check_hovered(&self, node_pos: Vec2, node_radius: f32, timer_radius: f32) -> bool {
-        let mouse_pos = Vec2::from(mouse_position());
+    pub fn check_hovered(
+        &self,
+        mouse_pos: Vec2,
+        node_pos: Vec2,
+        node_radius: f32,
+        timer_radius: f32,
+    ) -> bool {
         calc_dist(
             self.get_position(node_pos, node_radius, timer_radius),
             mouse_pos,
         ) <= timer_radius
     }
 
-    pub fn draw(&self, node_pos: Vec2, state: &State) {
+    pub fn draw(&self, node_pos: Vec2, state: &State, batch: &mut ShapeBatch) {
         let pos = self.get_position(node_pos, state.get_node_radius(), state.get_timer_radius());
-        let mut color = TIMER_COLOR;
-        let duration = state.current_time - self.time_set;
-        if state.current_time >= self.time_removed * 0.95 {
-            color = if self.time_removed < self.time_set + self.delay {
-                CANCELLED_TIMER_COLOR
-            } else {
-                READY_TIMER_COLOR
-            };
+        let theme = &config().theme;
+        let timer = &self.timer;
+        let mut color = theme.timer.0;
+        let duration = state.current_time - timer.time_set;
+        if let Some(removed) = timer.time_removed {
+            if state.current_time >= removed - timer.delay * 0.05 {
+                color = if removed < timer.time_set + timer.delay {
+                    theme.cancelled_timer.0
+                } else {
+                    theme.ready_timer.0
+                };
+            }
         }
-        let end_angle = (duration * 2. * (PI as f64) / self.delay) as f32 - PI / 2.;
-        draw_circle_segment(
-            pos.x,
-            pos.y,
-            state.get_timer_radius(),
-            -PI / 2.,
-            end_angle as f32,
-            color,
-        );
-        draw_circle_lines(pos.x, pos.y, state.get_timer_radius(), 2., color)
+        let end_angle = (duration * 2. * (PI as f64) / timer.delay) as f32 - PI / 2.;
+        let radius = state.get_timer_radius();
+        let segments = state.circle_segments(radius);
+        batch.sector(pos, radius, -PI / 2., end_angle, segments, color);
+        batch.arc(pos, radius, 2., 0., 2. * PI, segments, color);
     }
 
-    pub fn draw_ui(&self, egui_ctx: &Context) {
+    pub fn draw_ui(&self, egui_ctx: &Context, id: &str) {
         let default_pos = (screen_width() * 0.8, screen_height() * 0.8);
-        egui::Window::new(format!("Timer {}", self.id))
+        egui::Window::new(format!("Timer {}", id))
             .default_pos(default_pos)
             .show(egui_ctx, |ui| {
-                ui.label(format!("Name: {}", self.name));
-                ui.label(format!("Timer delay: {}", self.delay));
-                ui.label(format!("Time set: {:.7}", self.time_set));
-                ui.label(format!("Time removed: {:.7}", self.time_removed));
+                let timer = &self.timer;
+                ui.label(format!("Name: {}", timer.name));
+                ui.label(format!("Timer delay: {}", format_duration(timer.delay)));
+                ui.label(format!("Time set: {}", format_time(timer.time_set)));
+                if let Some(removed) = timer.time_removed {
+                    ui.label(format!("Time removed: {}", format_time(removed)));
+                }
             });
     }
 }
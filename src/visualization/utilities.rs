@@ -1,63 +1,214 @@
+use dslab_trace::{log_entities::NetworkParams, SimTime};
 use macroquad::prelude::*;
 use serde_json::Value;
 
-pub const DEFAULT_NODE_RADIUS: f32 = 15.;
-pub const DEFAULT_MESSAGE_RADIUS: f32 = 5.;
-pub const CIRCLE_RADIUS: f32 = 160.;
-pub const DEFAULT_TIMER_RADIUS: f32 = 8.;
-pub const PARTITIONED_CIRCLE_RADIUS: f32 = 100.;
+use super::config::config;
 
-pub const DEFAULT_NODE_COLOR: Color = YELLOW;
-pub const DEAD_NODE_COLOR: Color = MAROON;
+pub const MSG_TYPE_COLORS: [Color; 12] = [
+    ORANGE, SKYBLUE, LIME, PINK, GOLD, VIOLET, BEIGE, MAGENTA, GREEN, PURPLE, YELLOW, BLUE,
+];
+pub const LATENCY_COLORS: [Color; 5] = [GREEN, LIME, YELLOW, ORANGE, RED];
+pub const DROPPED_LATENCY_COLOR: Color = GRAY;
 
-pub const TIMER_COLOR: Color = ORANGE;
-pub const READY_TIMER_COLOR: Color = GREEN;
-pub const CANCELLED_TIMER_COLOR: Color = RED;
+pub const CONFIG_DIR_NAME: &str = "dslab-visualization";
+pub const TYPE_COLORS_FILE_NAME: &str = "type_colors.json";
+pub const CONFIG_FILE_NAME: &str = "config";
+pub const LOCAL_CONFIG_FILE_NAME: &str = "dslab-visualization";
+pub const LAYOUT_FILE_SUFFIX: &str = ".layout.json";
 
-pub const GLOBAL_SPEED_DELTA: f32 = 0.0002;
-pub const SCALE_COEF_DELTA: f32 = 0.05;
-pub const MAX_MESSAGE_SPEED: f32 = 30.;
 pub const DEFAULT_GLOBAL_SPEED: f32 = 0.001;
 
 pub const SINGLE_CLICK_DELAY: f64 = 0.12;
 
-pub const TIMERS_MAX_NUMBER: usize = 9;
+pub const LAYOUT_ANIMATION_SPEED: f32 = 4.;
+/// Seconds of real time a node stays highlighted after it is picked in a window.
+pub const HIGHLIGHT_DURATION: f64 = 2.;
+/// Share of the way to the destination that a lost message flies before it disappears.
+pub const LOST_MESSAGE_PATH_SHARE: f32 = 0.25;
+pub const PARTITION_REGION_MARGIN: f32 = 10.;
+/// Queued messages drawn in the stack next to a node, the rest are only counted.
+pub const MAX_SHOWN_QUEUED_MESSAGES: usize = 5;
+/// Payloads whose line diff needs a larger table than this are shown side by side instead.
+pub const MAX_DIFF_TABLE_SIZE: usize = 1_000_000;
+
+pub const MIN_ZOOM: f32 = 0.02;
+pub const MAX_ZOOM: f32 = 50.;
+pub const ZOOM_WHEEL_FACTOR: f32 = 1.1;
+pub const FIT_MARGIN: f32 = 60.;
+pub const MAX_FONT_SIZE: u16 = 128;
+/// Labels of nodes and messages smaller than this on the screen (in pixels) are not drawn.
+pub const LABEL_MIN_SCREEN_SIZE: f32 = 8.;
+
+pub const BATCH_MAX_VERTICES: usize = 8000;
+pub const BATCH_MAX_INDICES: usize = 4800;
 
 pub fn calc_dist(a: Vec2, b: Vec2) -> f32 {
     ((a.x - b.x) * (a.x - b.x) + (a.y - b.y) * (a.y - b.y)).sqrt()
 }
 
-pub fn get_relative_pos(pos: Vec2) -> Vec2 {
-    Vec2 {
-        x: pos.x / screen_width(),
-        y: pos.y / screen_height(),
+/// Draws text centered at `center` under the active camera, rasterized for the current zoom
+/// so that it stays sharp.
+pub fn draw_centered_text(text: &str, center: Vec2, world_font_size: f32, color: Color) {
+    let (mut font_size, mut font_scale, font_scale_aspect) = camera_font_scale(world_font_size);
+    if font_size == 0 {
+        return;
     }
+    if font_size > MAX_FONT_SIZE {
+        font_scale *= font_size as f32 / MAX_FONT_SIZE as f32;
+        font_size = MAX_FONT_SIZE;
+    }
+    let size = measure_text(text, None, font_size, font_scale);
+    draw_text_ex(
+        text,
+        center.x - size.width / 2.0,
+        center.y + size.height / 2.0,
+        TextParams {
+            font_size,
+            font_scale,
+            font_scale_aspect,
+            color,
+            ..Default::default()
+        },
+    );
 }
 
-pub fn get_absolute_pos(relative_pos: Vec2) -> Vec2 {
-    Vec2 {
-        x: relative_pos.x * screen_width(),
-        y: relative_pos.y * screen_height(),
-    }
+/// Formats a moment of simulated time in the units and precision from the config.
+pub fn format_time(time: SimTime) -> String {
+    time.display(config().time_unit, config().time_precision)
+}
+
+/// Formats a duration in seconds like `format_time`.
+pub fn format_duration(secs: f64) -> String {
+    format_time(SimTime::from_secs(secs))
+}
+
+/// Describes network parameters in one line, unset ones are shown as "-".
+pub fn format_network_params(params: &NetworkParams) -> String {
+    let delay = |delay: Option<f64>| delay.map_or("-".to_string(), format_duration);
+    let rate = |rate: Option<f64>| rate.map_or("-".to_string(), |rate| format!("{}%", rate * 100.));
+    format!(
+        "delay {} .. {}, drop {}, dup {}, corrupt {}",
+        delay(params.min_delay),
+        delay(params.max_delay),
+        rate(params.drop_rate),
+        rate(params.duplication_rate),
+        rate(params.corruption_rate)
+    )
 }
 
+/// Pretty-prints JSON. Anything else, e.g. a corrupted payload, is returned as is.
 pub fn prettify_json_string(str: String) -> String {
-    let value: Value = serde_json::from_str(&str).unwrap();
-    serde_json::to_string_pretty(&value).unwrap()
-}
-
-pub fn draw_circle_segment(x: f32, y: f32, r: f32, start_angle: f32, end_angle: f32, color: Color) {
-    let num_segments = 100;
-    let theta = (end_angle - start_angle) / num_segments as f32;
-    for i in 0..num_segments {
-        let angle_start = start_angle + theta * i as f32;
-        let angle_end = start_angle + theta * (i + 1) as f32;
-        let start_x = x + r * angle_start.cos();
-        let start_y = y + r * angle_start.sin();
-        let end_x = x + r * angle_end.cos();
-        let end_y = y + r * angle_end.sin();
-        draw_line(start_x, start_y, end_x, end_y, 2.0, color);
-        draw_line(x, y, start_x, start_y, 2.0, color);
-        draw_line(x, y, end_x, end_y, 2.0, color);
+    match serde_json::from_str::<Value>(&str) {
+        Ok(value) => serde_json::to_string_pretty(&value).unwrap(),
+        Err(_) => str,
+    }
+}
+
+pub fn egui_color(color: Color) -> egui::Color32 {
+    egui::Color32::from_rgb(
+        (color.r * 255.) as u8,
+        (color.g * 255.) as u8,
+        (color.b * 255.) as u8,
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Line diff of two texts by their longest common subsequence of lines.
+/// Returns `None` if the texts are too long, see `MAX_DIFF_TABLE_SIZE`.
+pub fn diff_lines(old: &str, new: &str) -> Option<Vec<DiffLine>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let (n, m) = (old.len(), new.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_DIFF_TABLE_SIZE {
+        return None;
+    }
+    let mut common = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < n && j < m {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i].to_owned()));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i].to_owned()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j].to_owned()));
+            j += 1;
+        }
+    }
+    diff.extend(
+        old[i..]
+            .iter()
+            .map(|line| DiffLine::Removed(line.to_string())),
+    );
+    diff.extend(
+        new[j..]
+            .iter()
+            .map(|line| DiffLine::Added(line.to_string())),
+    );
+    Some(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(line: &str) -> DiffLine {
+        DiffLine::Same(line.to_owned())
+    }
+
+    fn removed(line: &str) -> DiffLine {
+        DiffLine::Removed(line.to_owned())
+    }
+
+    fn added(line: &str) -> DiffLine {
+        DiffLine::Added(line.to_owned())
+    }
+
+    #[test]
+    fn diff_keeps_common_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nx\nc\nd"),
+            Some(vec![
+                same("a"),
+                removed("b"),
+                added("x"),
+                same("c"),
+                added("d")
+            ])
+        );
+    }
+
+    #[test]
+    fn diff_of_empty_texts() {
+        assert_eq!(diff_lines("", ""), Some(Vec::new()));
+        assert_eq!(diff_lines("", "a\nb"), Some(vec![added("a"), added("b")]));
+        assert_eq!(diff_lines("a", ""), Some(vec![removed("a")]));
+    }
+
+    #[test]
+    fn diff_of_too_long_texts_is_not_computed() {
+        let lines = |count: usize| vec!["line"; count].join("\n");
+        // the table has a row and a column more than the texts have lines
+        let side = (MAX_DIFF_TABLE_SIZE as f64).sqrt() as usize - 1;
+        assert!(diff_lines(&lines(side), &lines(side)).is_some());
+        assert_eq!(diff_lines(&lines(side + 1), &lines(side)), None);
     }
 }